@@ -0,0 +1,667 @@
+//! Compact binary graph format (`.pgb`).
+//!
+//! The file starts with the magic `PGBF` and a little-endian `u32` format
+//! version, followed by sections of the form `kind: u8, len: u64, payload`.
+//! Integers inside a payload are LEB128 varints, strings are length-prefixed.
+//! Links are stored as adjacency lists of their source segments. Sections
+//! not requested by the `GFAParser` are skipped without decoding.
+use crate::error::CmdError;
+use crate::gfa::{self, GFAParser, GfaEntity, GfaWritable, Tag, GFA};
+use bstr::io::BufReadExt;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, Read, Write};
+
+pub const MAGIC: &[u8; 4] = b"PGBF";
+pub const FORMAT_VERSION: u32 = 3;
+
+const SECTION_HEADER: u8 = b'H';
+const SECTION_DICT: u8 = b'D';
+const SECTION_SEGMENTS: u8 = b'S';
+const SECTION_LINKS: u8 = b'L';
+const SECTION_WALKS: u8 = b'W';
+const SECTION_PATHS: u8 = b'P';
+
+const SEQ_RAW: u8 = 0;
+const SEQ_2BIT: u8 = 1;
+
+/// Check whether the file at `path` starts with the binary graph magic.
+pub fn is_binary<P: AsRef<std::path::Path>>(path: P) -> Result<bool, CmdError> {
+    let mut file = File::open(path).map_err(CmdError::FileOpenError)?;
+    let mut magic = [0u8; 4];
+    match file.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(CmdError::LineReadError(e)),
+    }
+}
+
+/// Fail with a clear error when a command that needs the byte layout of a
+/// GFA file is given a binary graph.
+pub fn require_text(path: &str) -> Result<(), CmdError> {
+    if is_binary(path)? {
+        return Err(CmdError::InvalidArgument(format!(
+            "{} is a binary graph, convert it with --to gfa first",
+            path
+        )));
+    }
+    Ok(())
+}
+
+/// Hand every line of a GFA file to `f`, or every record of a binary graph
+/// written as a GFA line, so that line-based commands read either.
+pub fn for_each_line(
+    path: &str,
+    mut f: impl FnMut(&[u8]) -> Result<(), CmdError>,
+) -> Result<(), CmdError> {
+    let file = File::open(path).map_err(CmdError::FileOpenError)?;
+    if !is_binary(path)? {
+        for line in BufReader::new(file).byte_lines() {
+            f(&line.map_err(CmdError::LineReadError)?)?;
+        }
+        return Ok(());
+    }
+    let mut line = Vec::new();
+    read_entities(&mut BufReader::new(file), &GFAParser::default(), |entity| {
+        if let GfaEntity::Header(header) = &entity {
+            if header.version.is_empty() && header.tags.is_empty() {
+                return Ok(());
+            }
+        }
+        line.clear();
+        entity.write_line(&mut line)?;
+        line.pop();
+        f(&line)
+    })
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn put_tags(buf: &mut Vec<u8>, tags: &[Tag]) {
+    put_varint(buf, tags.len() as u64);
+    for tag in tags {
+        put_bytes(buf, tag.to_string().as_bytes());
+    }
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+
+fn base_code(base: u8) -> Option<u8> {
+    match base {
+        b'A' => Some(0),
+        b'C' => Some(1),
+        b'G' => Some(2),
+        b'T' => Some(3),
+        _ => None,
+    }
+}
+
+fn put_sequence(buf: &mut Vec<u8>, sequence: &[u8]) {
+    if sequence.is_empty() || sequence.iter().any(|&b| base_code(b).is_none()) {
+        buf.push(SEQ_RAW);
+        put_bytes(buf, sequence);
+        return;
+    }
+    buf.push(SEQ_2BIT);
+    put_varint(buf, sequence.len() as u64);
+    for chunk in sequence.chunks(4) {
+        let mut packed = 0u8;
+        for (i, &b) in chunk.iter().enumerate() {
+            packed |= base_code(b).unwrap_or(0) << (i * 2);
+        }
+        buf.push(packed);
+    }
+}
+
+/// Delta and varint encode an oriented step list.
+fn put_steps(buf: &mut Vec<u8>, steps: gfa::StepIterator) {
+    let steps: Vec<gfa::Step> = steps.collect();
+    put_varint(buf, steps.len() as u64);
+    let mut prev = 0i64;
+    for step in steps {
+        let delta = step.id as i64 - prev;
+        prev = step.id as i64;
+        put_varint(buf, (zigzag(delta) << 1) | u64::from(!step.forward));
+    }
+}
+
+struct Dictionary {
+    names: Vec<String>,
+    index: HashMap<String, u64>,
+}
+
+impl Dictionary {
+    fn new() -> Self {
+        Dictionary {
+            names: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+    fn get(&mut self, name: &str) -> u64 {
+        if let Some(&idx) = self.index.get(name) {
+            return idx;
+        }
+        let idx = self.names.len() as u64;
+        self.names.push(name.to_owned());
+        self.index.insert(name.to_owned(), idx);
+        idx
+    }
+}
+
+fn write_section<W: Write>(writer: &mut W, kind: u8, payload: &[u8]) -> Result<(), CmdError> {
+    writer
+        .write_all(&[kind])
+        .map_err(|_| CmdError::WriteError)?;
+    writer
+        .write_all(&(payload.len() as u64).to_le_bytes())
+        .map_err(|_| CmdError::WriteError)?;
+    writer.write_all(payload).map_err(|_| CmdError::WriteError)
+}
+
+/// Encode a parsed graph into the binary format.
+pub fn write_gfa<W: Write>(gfa: &GFA, writer: &mut W) -> Result<(), CmdError> {
+    writer.write_all(MAGIC).map_err(|_| CmdError::WriteError)?;
+    writer
+        .write_all(&FORMAT_VERSION.to_le_bytes())
+        .map_err(|_| CmdError::WriteError)?;
+
+    let mut buf = Vec::new();
+    put_bytes(&mut buf, gfa.headers.version.as_bytes());
+    put_tags(&mut buf, &gfa.headers.tags);
+    write_section(writer, SECTION_HEADER, &buf)?;
+    buf.clear();
+
+    let mut samples = Dictionary::new();
+    let mut haptypes = Dictionary::new();
+    let mut contigs = Dictionary::new();
+
+    put_varint(&mut buf, gfa.segments.len() as u64);
+    for segment in &gfa.segments {
+        put_varint(&mut buf, segment.id as u64);
+        put_sequence(&mut buf, &segment.sequence);
+        put_tags(&mut buf, &segment.tags);
+    }
+    let segments = std::mem::take(&mut buf);
+
+    // outgoing links of every source segment, in order of first appearance
+    let mut adjacency: Vec<(usize, Vec<&gfa::Link>)> = Vec::new();
+    let mut sources: HashMap<usize, usize> = HashMap::new();
+    for link in &gfa.links {
+        let i = *sources.entry(link.from_segment).or_insert_with(|| {
+            adjacency.push((link.from_segment, Vec::new()));
+            adjacency.len() - 1
+        });
+        adjacency[i].1.push(link);
+    }
+    put_varint(&mut buf, adjacency.len() as u64);
+    for (from, links) in &adjacency {
+        put_varint(&mut buf, *from as u64);
+        put_varint(&mut buf, links.len() as u64);
+        for link in links {
+            put_varint(&mut buf, link.to_segment as u64);
+            buf.push(u8::from(link.from_orient) | (u8::from(link.to_orient) << 1));
+            put_bytes(&mut buf, &link.overlap);
+            put_tags(&mut buf, &link.tags);
+        }
+    }
+    let links = std::mem::take(&mut buf);
+
+    if let Some(name) = gfa
+        .walks
+        .iter()
+        .filter(|w| w.is_lazy())
+        .map(|w| w.name())
+        .chain(gfa.paths.iter().filter(|p| p.is_lazy()).map(|p| p.name()))
+        .next()
+    {
+        return Err(CmdError::InvalidArgument(format!(
+            "{} has the lazy steps of an index, load them before writing",
+            name
+        )));
+    }
+    put_varint(&mut buf, gfa.walks.len() as u64);
+    for walk in &gfa.walks {
+        put_varint(&mut buf, samples.get(&walk.sample));
+        put_varint(&mut buf, haptypes.get(&walk.haptype));
+        put_varint(&mut buf, contigs.get(&walk.chroms));
+        put_varint(&mut buf, walk.ranges.start as u64);
+        put_varint(&mut buf, walk.ranges.end as u64);
+        put_steps(&mut buf, walk.extract_step());
+        put_tags(&mut buf, &walk.tags);
+    }
+    let walks = std::mem::take(&mut buf);
+
+    put_varint(&mut buf, gfa.paths.len() as u64);
+    for path in &gfa.paths {
+        put_varint(&mut buf, samples.get(&path.sample));
+        put_varint(&mut buf, haptypes.get(&path.haptype.to_string()));
+        put_varint(&mut buf, contigs.get(&path.chroms));
+        match path.ranges {
+            Some(r) => {
+                buf.push(1);
+                put_varint(&mut buf, r.start as u64);
+                put_varint(&mut buf, r.end as u64);
+            }
+            None => buf.push(0),
+        }
+        put_steps(&mut buf, path.extract_step());
+        match &path.overlaps {
+            Some(overlaps) => {
+                buf.push(1);
+                put_bytes(&mut buf, overlaps);
+            }
+            None => buf.push(0),
+        }
+        put_tags(&mut buf, &path.tags);
+    }
+    let paths = std::mem::take(&mut buf);
+
+    for dict in [&samples, &haptypes, &contigs] {
+        put_varint(&mut buf, dict.names.len() as u64);
+        for name in &dict.names {
+            put_bytes(&mut buf, name.as_bytes());
+        }
+    }
+    write_section(writer, SECTION_DICT, &buf)?;
+    write_section(writer, SECTION_SEGMENTS, &segments)?;
+    write_section(writer, SECTION_LINKS, &links)?;
+    write_section(writer, SECTION_WALKS, &walks)?;
+    write_section(writer, SECTION_PATHS, &paths)?;
+    writer.flush().map_err(|_| CmdError::WriteError)
+}
+
+fn truncated() -> CmdError {
+    CmdError::BinaryFormatError("unexpected end of data".to_string())
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Decoder { data, pos: 0 }
+    }
+
+    fn byte(&mut self) -> Result<u8, CmdError> {
+        let b = *self.data.get(self.pos).ok_or_else(truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn varint(&mut self) -> Result<u64, CmdError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.byte()?;
+            if shift >= 64 {
+                return Err(CmdError::BinaryFormatError("varint overflow".to_string()));
+            }
+            value |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn usize(&mut self) -> Result<usize, CmdError> {
+        Ok(self.varint()? as usize)
+    }
+
+    /// Read a number of items, each taking at least one byte, so a corrupt
+    /// count fails here instead of reserving memory for it.
+    fn count(&mut self) -> Result<usize, CmdError> {
+        let n = self.usize()?;
+        if n > self.data.len() - self.pos {
+            return Err(truncated());
+        }
+        Ok(n)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CmdError> {
+        let end = self.pos.checked_add(len).ok_or_else(truncated)?;
+        let slice = self.data.get(self.pos..end).ok_or_else(truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], CmdError> {
+        let len = self.usize()?;
+        self.take(len)
+    }
+
+    fn string(&mut self) -> Result<String, CmdError> {
+        Ok(String::from_utf8_lossy(self.bytes()?).into_owned())
+    }
+
+    fn tags(&mut self) -> Result<Vec<Tag>, CmdError> {
+        let n = self.count()?;
+        (0..n).map(|_| Tag::parse(self.bytes()?)).collect()
+    }
+
+    fn sequence(&mut self) -> Result<Vec<u8>, CmdError> {
+        match self.byte()? {
+            SEQ_RAW => Ok(self.bytes()?.to_vec()),
+            SEQ_2BIT => {
+                let len = self.usize()?;
+                let packed = self.take(len.div_ceil(4))?;
+                let mut sequence = Vec::with_capacity(len);
+                for i in 0..len {
+                    let code = (packed[i / 4] >> ((i % 4) * 2)) & 0b11;
+                    sequence.push(b"ACGT"[code as usize]);
+                }
+                Ok(sequence)
+            }
+            other => Err(CmdError::BinaryFormatError(format!(
+                "unknown sequence encoding {}",
+                other
+            ))),
+        }
+    }
+
+    /// Decode a step list back into its W-line (`>11<12`) or P-line
+    /// (`11+,12-`) text form.
+    fn steps(&mut self, walk_style: bool) -> Result<Vec<u8>, CmdError> {
+        let n = self.count()?;
+        let mut steps = Vec::with_capacity(n);
+        let mut prev = 0i64;
+        for _ in 0..n {
+            let value = self.varint()?;
+            let id = prev + unzigzag(value >> 1);
+            prev = id;
+            steps.push(gfa::Step {
+                id: id as usize,
+                forward: value & 1 == 0,
+            });
+        }
+        Ok(if walk_style {
+            gfa::walk_unit(steps)
+        } else {
+            gfa::path_unit(steps)
+        })
+    }
+}
+
+fn lookup(dict: &[String], idx: usize) -> Result<&String, CmdError> {
+    dict.get(idx).ok_or_else(|| {
+        CmdError::BinaryFormatError(format!("dictionary index {} out of range", idx))
+    })
+}
+
+/// Decode a binary graph, keeping only the record types enabled in `parser`.
+pub fn read_gfa<R: Read>(reader: &mut R, parser: &GFAParser) -> Result<GFA, CmdError> {
+    let mut gfa = GFA::new();
+    read_entities(reader, parser, |entity| {
+        gfa.add_entity(entity);
+        Ok(())
+    })?;
+    Ok(gfa)
+}
+
+/// Decode a binary graph a section at a time, handing the records of the
+/// types enabled in `parser` to `f`: the header, segments, links, walks and
+/// paths.
+pub fn read_entities<R: Read>(
+    reader: &mut R,
+    parser: &GFAParser,
+    mut f: impl FnMut(GfaEntity) -> Result<(), CmdError>,
+) -> Result<(), CmdError> {
+    let mut magic = [0u8; 4];
+    reader
+        .read_exact(&mut magic)
+        .map_err(CmdError::LineReadError)?;
+    if &magic != MAGIC {
+        return Err(CmdError::BinaryFormatError("bad magic".to_string()));
+    }
+    let mut version = [0u8; 4];
+    reader
+        .read_exact(&mut version)
+        .map_err(CmdError::LineReadError)?;
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(CmdError::BinaryFormatError(format!(
+            "unsupported format version {}, expected {}",
+            version, FORMAT_VERSION
+        )));
+    }
+
+    let mut dicts: Vec<Vec<String>> = vec![Vec::new(); 3];
+    loop {
+        let mut kind = [0u8; 1];
+        match reader.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(CmdError::LineReadError(e)),
+        }
+        let mut len = [0u8; 8];
+        reader
+            .read_exact(&mut len)
+            .map_err(CmdError::LineReadError)?;
+        let len = u64::from_le_bytes(len);
+        let wanted = match kind[0] {
+            SECTION_HEADER | SECTION_DICT => true,
+            SECTION_SEGMENTS => parser.segments,
+            SECTION_LINKS => parser.links,
+            SECTION_WALKS => parser.walks,
+            SECTION_PATHS => parser.paths,
+            _ => false,
+        };
+        if !wanted {
+            std::io::copy(&mut reader.by_ref().take(len), &mut std::io::sink())
+                .map_err(CmdError::LineReadError)?;
+            continue;
+        }
+        let mut payload = Vec::new();
+        reader
+            .by_ref()
+            .take(len)
+            .read_to_end(&mut payload)
+            .map_err(CmdError::LineReadError)?;
+        if payload.len() as u64 != len {
+            return Err(truncated());
+        }
+        let mut d = Decoder::new(&payload);
+        match kind[0] {
+            SECTION_HEADER => {
+                let version = d.string()?;
+                let tags = d.tags()?;
+                f(GfaEntity::Header(gfa::Header::from_tags(version, tags)))?;
+            }
+            SECTION_DICT => {
+                for dict in dicts.iter_mut() {
+                    let n = d.count()?;
+                    *dict = (0..n).map(|_| d.string()).collect::<Result<_, _>>()?;
+                }
+            }
+            SECTION_SEGMENTS => {
+                let n = d.count()?;
+                for _ in 0..n {
+                    let id = d.usize()?;
+                    let sequence = d.sequence()?;
+                    let tags = d.tags()?;
+                    f(GfaEntity::Segment(gfa::Segment { id, sequence, tags }))?;
+                }
+            }
+            SECTION_LINKS => {
+                let n = d.count()?;
+                for _ in 0..n {
+                    let from_segment = d.usize()?;
+                    let m = d.count()?;
+                    for _ in 0..m {
+                        let to_segment = d.usize()?;
+                        let orient = d.byte()?;
+                        let overlap = d.bytes()?.to_vec();
+                        let tags = d.tags()?;
+                        f(GfaEntity::Link(gfa::Link {
+                            from_segment,
+                            from_orient: orient & 1 != 0,
+                            to_segment,
+                            to_orient: orient & 2 != 0,
+                            overlap,
+                            tags,
+                        }))?;
+                    }
+                }
+            }
+            SECTION_WALKS => {
+                let n = d.count()?;
+                for _ in 0..n {
+                    let sample = lookup(&dicts[0], d.usize()?)?.clone();
+                    let haptype = lookup(&dicts[1], d.usize()?)?.clone();
+                    let chroms = lookup(&dicts[2], d.usize()?)?.clone();
+                    let start = d.usize()?;
+                    let end = d.usize()?;
+                    let unit = d.steps(true)?;
+                    let tags = d.tags()?;
+                    f(GfaEntity::Walk(gfa::Walk {
+                        sample,
+                        haptype,
+                        chroms,
+                        ranges: gfa::Range { start, end },
+                        unit,
+                        tags,
+                    }))?;
+                }
+            }
+            SECTION_PATHS => {
+                let n = d.count()?;
+                for _ in 0..n {
+                    let sample = lookup(&dicts[0], d.usize()?)?.clone();
+                    let haptype = lookup(&dicts[1], d.usize()?)?
+                        .parse::<u8>()
+                        .map_err(|_| CmdError::ParseError)?;
+                    let chroms = lookup(&dicts[2], d.usize()?)?.clone();
+                    let ranges = if d.byte()? == 1 {
+                        let start = d.usize()?;
+                        let end = d.usize()?;
+                        Some(gfa::Range { start, end })
+                    } else {
+                        None
+                    };
+                    let unit = d.steps(false)?;
+                    let overlaps = if d.byte()? == 1 {
+                        Some(d.bytes()?.to_vec())
+                    } else {
+                        None
+                    };
+                    let tags = d.tags()?;
+                    f(GfaEntity::Path(gfa::Path {
+                        sample,
+                        haptype,
+                        chroms,
+                        ranges,
+                        unit,
+                        overlaps,
+                        tags,
+                    }))?;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Decode the binary graph stored at `path`.
+pub fn read_file<P: AsRef<std::path::Path>>(path: P, parser: &GFAParser) -> Result<GFA, CmdError> {
+    let file = File::open(path).map_err(CmdError::FileOpenError)?;
+    read_gfa(&mut BufReader::new(file), parser)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfa::GFAParserBuilder;
+
+    #[test]
+    fn test_binary_round_trip() {
+        let gfa_data: &[u8] = b"H\tVN:Z:1.1\tRS:Z:sample\n\
+            S\t11\tACCTTG\n\
+            S\t12\tTCANGG\tLN:i:6\n\
+            S\t130\t*\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t12\t-\t130\t+\t*\tRC:i:2\n\
+            P\tsample#1#chr2:5-16\t130+,11-\t0M\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>130<11\n";
+        let parser = gfa::GFAParser::default();
+        let mut gfa = GFA::new();
+        for line in gfa_data.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            gfa.add_entity(parser.parse_gfa_line(line).unwrap().unwrap());
+        }
+
+        let mut encoded = Vec::new();
+        write_gfa(&gfa, &mut encoded).unwrap();
+        let decoded = read_gfa(&mut encoded.as_slice(), &parser).unwrap();
+        let mut written = Vec::new();
+        decoded.write_to(&mut written).unwrap();
+        assert_eq!(written, gfa_data);
+        assert_eq!(decoded.headers.samples, Some(vec!["sample".to_string()]));
+
+        let walks_only = GFAParserBuilder::new().get_walks(true).build();
+        let decoded = read_gfa(&mut encoded.as_slice(), &walks_only).unwrap();
+        assert!(decoded.segments.is_empty());
+        assert!(decoded.paths.is_empty());
+        assert_eq!(
+            decoded.walks[0].extract_node().collect::<Vec<_>>(),
+            vec![11, 12, 130, 11]
+        );
+    }
+
+    #[test]
+    fn test_binary_lazy_and_corrupt() {
+        let parser = gfa::GFAParser::default();
+        let mut gfa = GFA::new();
+        let line = b"W\tsample\t0\tchr1\t0\t18\t*\tbo:i:34";
+        gfa.add_entity(parser.parse_gfa_line(line).unwrap().unwrap());
+        let mut encoded = Vec::new();
+        assert!(matches!(
+            write_gfa(&gfa, &mut encoded),
+            Err(CmdError::InvalidArgument(_))
+        ));
+        let mut encoded = Vec::new();
+        write_gfa(&GFA::new(), &mut encoded).unwrap();
+
+        // a section length far past the end of the data
+        let mut corrupt = encoded[..8].to_vec();
+        corrupt.push(SECTION_HEADER);
+        corrupt.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(read_gfa(&mut corrupt.as_slice(), &parser).is_err());
+
+        // a step count far past the end of the section
+        let mut dict = Vec::new();
+        for _ in 0..3 {
+            put_varint(&mut dict, 1);
+            put_bytes(&mut dict, b"x");
+        }
+        let mut walks = Vec::new();
+        put_varint(&mut walks, 1);
+        walks.extend_from_slice(&[0, 0, 0, 0, 0]);
+        put_varint(&mut walks, u64::MAX - 1);
+        let mut corrupt = encoded[..8].to_vec();
+        write_section(&mut corrupt, SECTION_DICT, &dict).unwrap();
+        write_section(&mut corrupt, SECTION_WALKS, &walks).unwrap();
+        assert!(matches!(
+            read_gfa(&mut corrupt.as_slice(), &parser),
+            Err(CmdError::BinaryFormatError(_))
+        ));
+    }
+}
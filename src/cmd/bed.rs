@@ -0,0 +1,147 @@
+use crate::{error::CmdError, gfa};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+/// Write one BED record per step of `steps`, starting at `start` on `contig`.
+fn write_steps<W: Write>(
+    writer: &mut W,
+    contig: &str,
+    start: usize,
+    steps: gfa::StepIterator,
+    lengths: &HashMap<usize, usize>,
+    nodes: &Option<HashSet<usize>>,
+) -> Result<(), CmdError> {
+    let mut pos = start;
+    for step in steps {
+        let len = *lengths.get(&step.id).ok_or_else(|| {
+            CmdError::InvalidArgument(format!("segment {} on {} has no S-line", step.id, contig))
+        })?;
+        if nodes.as_ref().is_none_or(|n| n.contains(&step.id)) {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}",
+                contig,
+                pos,
+                pos + len,
+                step.id,
+                gfa::orient_char(step.forward)
+            )
+            .map_err(|_| CmdError::WriteError)?;
+        }
+        pos += len;
+    }
+    Ok(())
+}
+
+/// Write the position of every step of every walk and path as a BED record
+/// `contig start end node strand`, with PanSN contig names.
+///
+/// Positions start at the walk's start coordinate, or 0 for a path without
+/// a range. `sample` restricts the output to walks and paths of those
+/// samples and `node` to the nodes in the list.
+pub fn run(
+    gfa: String,
+    output: String,
+    sample: Vec<String>,
+    node: Option<String>,
+) -> Result<(), CmdError> {
+    let gfa_parser = gfa::GFAParserBuilder::new()
+        .get_segments(true)
+        .get_walks(true)
+        .get_paths(true)
+        .build();
+    let gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let nodes: Option<HashSet<usize>> = match node {
+        Some(path) => Some(super::read_node_list(&path)?.into_iter().collect()),
+        None => None,
+    };
+    let selected = |s: &String| sample.is_empty() || sample.contains(s);
+    let lengths = gfa_obj.get_segment_len();
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    for walk in gfa_obj.walks.iter().filter(|w| selected(&w.sample)) {
+        write_steps(
+            &mut writer,
+            &walk.name(),
+            walk.ranges.start,
+            walk.extract_step(),
+            &lengths,
+            &nodes,
+        )?;
+    }
+    for path in gfa_obj.paths.iter().filter(|p| selected(&p.sample)) {
+        let contig = format!("{}#{}#{}", path.sample, path.haptype, path.chroms);
+        let start = path.ranges.map_or(0, |r| r.start);
+        write_steps(
+            &mut writer,
+            &contig,
+            start,
+            path.extract_step(),
+            &lengths,
+            &nodes,
+        )?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_bed() {
+        let temp_dir = TempDir::new("test_bed").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let node_file_path = temp_dir.path().join("test.nodes");
+        let output_file_path = temp_dir.path().join("output.bed");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            W\tsample1\t1\tchr1\t100\t118\t>11<12>13\n\
+            W\tsample2\t0\tchr1\t0\t12\t>11>13\n\
+            P\tsample3#0#chr2\t13-,11+\t*\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        writeln!(File::create(&node_file_path).unwrap(), "13").unwrap();
+
+        let gfa_file = gfa_file_path.to_str().unwrap().to_string();
+        let output = output_file_path.to_str().unwrap().to_string();
+        run(gfa_file.clone(), output.clone(), Vec::new(), None).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "sample1#1#chr1\t100\t105\t11\t+\n\
+             sample1#1#chr1\t105\t111\t12\t-\n\
+             sample1#1#chr1\t111\t118\t13\t+\n\
+             sample2#0#chr1\t0\t5\t11\t+\n\
+             sample2#0#chr1\t5\t12\t13\t+\n\
+             sample3#0#chr2\t0\t7\t13\t-\n\
+             sample3#0#chr2\t7\t12\t11\t+\n"
+        );
+
+        run(
+            gfa_file,
+            output.clone(),
+            vec!["sample1".to_string(), "sample3".to_string()],
+            Some(node_file_path.to_str().unwrap().to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "sample1#1#chr1\t111\t118\t13\t+\n\
+             sample3#0#chr2\t0\t7\t13\t-\n"
+        );
+    }
+}
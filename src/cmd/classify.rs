@@ -0,0 +1,170 @@
+use super::pav;
+use crate::error::CmdError;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+const CLASSES: [&str; 5] = ["core", "soft-core", "dispensable", "private", "absent"];
+
+/// Class of a node found in `frequency` of `width` columns.
+fn class_of(frequency: usize, width: usize, soft_core: f64, private: usize) -> usize {
+    if frequency == 0 {
+        4
+    } else if frequency == width {
+        0
+    } else if frequency as f64 >= soft_core * width as f64 {
+        1
+    } else if frequency <= private {
+        3
+    } else {
+        2
+    }
+}
+
+/// Classify every node of the graph by the number of samples, or
+/// haplotypes with `columns`, whose walks contain it: core (all of them),
+/// soft-core (at least `soft_core` of them), private (at most `private`)
+/// and dispensable (the rest); nodes no walk contains are absent.
+///
+/// Node and bp counts per class are written to `output`, overall and per
+/// chromosome of the walks containing the nodes, and the class of every
+/// node to `<output>.nodes`.
+pub fn run(
+    gfa: String,
+    output: String,
+    columns: String,
+    soft_core: f64,
+    private: usize,
+) -> Result<(), CmdError> {
+    if !(soft_core > 0.0 && soft_core <= 1.0) {
+        return Err(CmdError::InvalidArgument(format!(
+            "soft-core fraction {} is not in (0, 1]",
+            soft_core
+        )));
+    }
+    let (gfa_obj, lengths) = pav::load_graph(&gfa)?;
+    let columns = pav::Columns::new(&gfa_obj.walks, &columns, None)?;
+    let width = columns.width();
+    log::debug!("Classifying nodes over {} columns", width);
+
+    let mut chroms: BTreeMap<&str, usize> = BTreeMap::new();
+    for walk in &gfa_obj.walks {
+        let next = chroms.len();
+        chroms.entry(walk.chroms.as_str()).or_insert(next);
+    }
+    let mut node_chroms: HashMap<usize, Vec<usize>> = HashMap::new();
+    for walk in &gfa_obj.walks {
+        let c = chroms[walk.chroms.as_str()];
+        for node in walk.extract_node() {
+            let entry = node_chroms.entry(node).or_default();
+            if !entry.contains(&c) {
+                entry.push(c);
+            }
+        }
+    }
+
+    let mut overall = [(0u64, 0u64); CLASSES.len()];
+    let mut per_chrom = vec![[(0u64, 0u64); CLASSES.len()]; chroms.len()];
+    let mut node_writer = BufWriter::new(
+        File::create(format!("{}.nodes", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(node_writer, "node\tfrequency\tclass").map_err(|_| CmdError::WriteError)?;
+    let rows = pav::graph_nodes(&gfa_obj, &lengths);
+    columns.count_rows(&rows, |id, row| {
+        let frequency = row.iter().filter(|c| c.total() > 0).count();
+        let class = class_of(frequency, width, soft_core, private);
+        let len = lengths.get(&id).copied().unwrap_or(0) as u64;
+        overall[class].0 += 1;
+        overall[class].1 += len;
+        for &c in node_chroms.get(&id).into_iter().flatten() {
+            per_chrom[c][class].0 += 1;
+            per_chrom[c][class].1 += len;
+        }
+        writeln!(node_writer, "{}\t{}\t{}", id, frequency, CLASSES[class])
+            .map_err(|_| CmdError::WriteError)
+    })?;
+    node_writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    writeln!(writer, "chrom\tclass\tnodes\tbp").map_err(|_| CmdError::WriteError)?;
+    let tables = std::iter::once(("all", &overall))
+        .chain(chroms.iter().map(|(name, &c)| (*name, &per_chrom[c])));
+    for (chrom, table) in tables {
+        for (class, (nodes, bp)) in CLASSES.iter().zip(table) {
+            writeln!(writer, "{}\t{}\t{}\t{}", chrom, class, nodes, bp)
+                .map_err(|_| CmdError::WriteError)?;
+        }
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_classify() {
+        let temp_dir = TempDir::new("test_classify").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("classes.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tAA\n\
+            S\t15\tC\n\
+            W\tsample1\t0\tchr1\t0\t20\t>11>12>13>14\n\
+            W\tsample2\t0\tchr1\t0\t18\t>11>12>13\n\
+            W\tsample3\t0\tchr1\t0\t11\t>11>12\n\
+            W\tsample4\t0\tchr2\t0\t5\t>11\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            output.clone(),
+            "sample".to_string(),
+            0.75,
+            1,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "chrom\tclass\tnodes\tbp\n\
+             all\tcore\t1\t5\n\
+             all\tsoft-core\t1\t6\n\
+             all\tdispensable\t1\t7\n\
+             all\tprivate\t1\t2\n\
+             all\tabsent\t1\t1\n\
+             chr1\tcore\t1\t5\n\
+             chr1\tsoft-core\t1\t6\n\
+             chr1\tdispensable\t1\t7\n\
+             chr1\tprivate\t1\t2\n\
+             chr1\tabsent\t0\t0\n\
+             chr2\tcore\t1\t5\n\
+             chr2\tsoft-core\t0\t0\n\
+             chr2\tdispensable\t0\t0\n\
+             chr2\tprivate\t0\t0\n\
+             chr2\tabsent\t0\t0\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.nodes", output)).unwrap(),
+            "node\tfrequency\tclass\n\
+             11\t4\tcore\n\
+             12\t3\tsoft-core\n\
+             13\t2\tdispensable\n\
+             14\t1\tprivate\n\
+             15\t0\tabsent\n"
+        );
+    }
+}
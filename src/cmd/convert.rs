@@ -1,5 +1,7 @@
+use crate::binary;
 use crate::error::CmdError;
-use crate::gfa;
+use crate::gfa::{self, GfaWritable};
+use crate::jsonl;
 use bstr::io::BufReadExt;
 use bstr::ByteSlice;
 use log;
@@ -12,7 +14,7 @@ fn p2w(input: &[u8]) -> Vec<u8> {
     input
         .split(|&b| b == b',')
         .flat_map(|part| {
-            let (direction, slice) = match part.ends_with(&[b'+']) {
+            let (direction, slice) = match part.ends_with(b"+") {
                 true => (b'>', &part[..part.len() - 1]),
                 false => (b'<', &part[..part.len() - 1]),
             };
@@ -43,80 +45,146 @@ fn w2p(input: &[u8]) -> Vec<u8> {
     result
 }
 
+/// W-lines have no overlap column, so the P-line overlaps are carried in
+/// this tag and restored when converting back.
+const OVERLAP_TAG: &[u8] = b"ov:Z:";
+
+/// The tag carrying the overlaps of a P-line on the W-line it becomes. A `*`
+/// overlap is left out when other tags force the column back anyway.
+pub(crate) fn overlap_tag(overlaps: &[u8], has_tags: bool) -> Option<gfa::Tag> {
+    if overlaps == b"*" && has_tags {
+        return None;
+    }
+    gfa::Tag::parse(&[OVERLAP_TAG, overlaps].concat()).ok()
+}
+
 fn write_with_error_handling<W: Write>(writer: &mut W, data: &[u8]) -> Result<(), CmdError> {
     writer.write_all(data).map_err(|_| CmdError::WriteError)
 }
 
+/// First pass over the GFA: collect the length of every segment, so that
+/// P-lines can be measured regardless of where their S-lines appear.
+pub(crate) fn segment_lengths(path: &str) -> Result<HashMap<usize, usize>, CmdError> {
+    let mut all_segment: HashMap<usize, usize> = HashMap::new();
+    binary::for_each_line(path, |line| match line.starts_with(b"S") {
+        true => handle_s_line(line, &mut all_segment),
+        false => Ok(()),
+    })?;
+    Ok(all_segment)
+}
+
 pub fn convert_1_1(path: String, output: String) -> Result<(), CmdError> {
     log::info!("Converting from 1.0 to 1.1");
-    let file = File::open(&path).map_err(CmdError::FileOpenError)?;
-    let lines = BufReader::new(file).byte_lines();
+    let all_segment = segment_lengths(&path)?;
+    log::debug!("Collected the length of {} segments", all_segment.len());
     let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
     let mut output = BufWriter::new(file);
-    let mut all_segment: HashMap<usize, usize> = HashMap::new();
-
-    for line in lines {
-        let line = line.map_err(CmdError::LineReadError)?;
-        let l = line.as_bytes();
+    let mut mismatches = 0;
 
+    binary::for_each_line(&path, |l| {
         if l.starts_with(b"H") {
-            handle_header_line(&line, &mut output)?;
+            handle_header_line(l, &mut output)?;
         } else if l.starts_with(b"P") {
-            handle_p_line(&line, &mut output, &mut all_segment)?;
-        } else {
-            if l.starts_with(b"S") {
-                handle_s_line(&line, &mut all_segment)?;
+            if !handle_p_line(l, &mut output, &all_segment)? {
+                mismatches += 1;
             }
+        } else {
             write_with_error_handling(&mut output, l)?;
             write_with_error_handling(&mut output, b"\n")?;
         }
-    }
+        Ok(())
+    })?;
     output.flush().map_err(|_| CmdError::WriteError)?;
+    if mismatches > 0 {
+        log::warn!(
+            "{} paths have a declared range that disagrees with their segment lengths",
+            mismatches
+        );
+    }
     Ok(())
 }
 
-fn handle_header_line(line: &Vec<u8>, output: &mut BufWriter<File>) -> Result<(), CmdError> {
-    let new_line = line.replace(b"Z:1.0", b"Z:1.1");
-    output
-        .write_all(new_line.as_bytes())
-        .map_err(|_| CmdError::WriteError)?;
-    output.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
-    Ok(())
+/// Rewrite the value of the `VN` tag, leaving every other header field as is.
+fn write_header_version(
+    line: &[u8],
+    version: &[u8],
+    output: &mut BufWriter<File>,
+) -> Result<(), CmdError> {
+    for (i, field) in line.split(|&b| b == b'\t').enumerate() {
+        if i > 0 {
+            write_with_error_handling(output, b"\t")?;
+        }
+        if field.starts_with(b"VN:Z:") {
+            write_with_error_handling(output, b"VN:Z:")?;
+            write_with_error_handling(output, version)?;
+        } else {
+            write_with_error_handling(output, field)?;
+        }
+    }
+    write_with_error_handling(output, b"\n")
 }
 
+fn handle_header_line(line: &[u8], output: &mut BufWriter<File>) -> Result<(), CmdError> {
+    write_header_version(line, b"1.1", output)
+}
+
+/// Rewrite a P-line as a W-line, carrying the overlaps in an `ov:Z` tag and
+/// keeping any other tags. Returns `false` if the path declares a
+/// `:start-end` range that disagrees with the summed segment lengths.
 fn handle_p_line(
-    line: &Vec<u8>,
+    line: &[u8],
     output: &mut BufWriter<File>,
-    all_segment: &mut HashMap<usize, usize>,
-) -> Result<(), CmdError> {
+    all_segment: &HashMap<usize, usize>,
+) -> Result<bool, CmdError> {
     let l = line.as_bytes();
     let parts: Vec<&[u8]> = l.split(|&b| b == b'\t').collect();
     let tem: Vec<&[u8]> = parts[1].split(|&b| b == b'#').collect();
     let chrom_parts: Vec<&[u8]> = tem[2].split(|&b| b == b':').collect();
-    let (start, mut end) = if chrom_parts.len() > 1 {
+    let declared = if chrom_parts.len() > 1 {
         let range_parts: Vec<&[u8]> = chrom_parts[1].split(|&b| b == b'-').collect();
+        if range_parts.len() != 2 {
+            return Err(CmdError::ParseError);
+        }
         let start = gfa::u8_slice_to_usize(range_parts[0])?;
         let end = gfa::u8_slice_to_usize(range_parts[1])?;
-        (start, end)
+        Some((start, end))
     } else {
-        (0, 0)
+        None
     };
 
-    let new_w = p2w(parts[2]);
-
-    if end == 0 {
-        let end_vec: Vec<usize> = new_w
-            .clone()
-            .split(|&b| b == b'>' || b == b'<')
-            .filter_map(|slice| gfa::u8_slice_to_usize(slice).ok())
-            .collect();
-
-        end = end_vec[1..]
-            .iter()
-            .filter_map(|&x| all_segment.get(&x))
-            .map(|&x| x as usize)
-            .sum();
+    let mut length = 0;
+    for node in gfa::NodeIterator::new(parts[2]) {
+        match all_segment.get(&node) {
+            Some(len) => length += len,
+            None => log::warn!(
+                "Path {} references unknown segment {}",
+                String::from_utf8_lossy(parts[1]),
+                node
+            ),
+        }
     }
+    let (start, end, consistent) = match declared {
+        Some((start, end)) => {
+            let consistent = end.checked_sub(start) == Some(length);
+            if !consistent {
+                log::warn!(
+                    "Path {} declares {} bp but its segments sum to {} bp",
+                    String::from_utf8_lossy(parts[1]),
+                    end.saturating_sub(start),
+                    length
+                );
+            }
+            (start, end, consistent)
+        }
+        None => (0, length, true),
+    };
+
+    let (overlaps, tags) = match parts.get(3) {
+        Some(field) if gfa::Tag::parse(field).is_err() => (Some(*field), &parts[4..]),
+        _ => (None, &parts[3.min(parts.len())..]),
+    };
+
+    let new_w = p2w(parts[2]);
     write_with_error_handling(output, b"W\t")?;
     write_with_error_handling(output, tem[0])?;
     write_with_error_handling(output, b"\t")?;
@@ -129,55 +197,49 @@ fn handle_p_line(
     write_with_error_handling(output, end.to_string().as_bytes())?;
     write_with_error_handling(output, b"\t")?;
     write_with_error_handling(output, &new_w)?;
+    for tag in tags {
+        write_with_error_handling(output, b"\t")?;
+        write_with_error_handling(output, tag)?;
+    }
+    if let Some(tag) = overlaps.and_then(|o| overlap_tag(o, !tags.is_empty())) {
+        write!(output, "\t{}", tag).map_err(|_| CmdError::WriteError)?;
+    }
     write_with_error_handling(output, b"\n")?;
-    Ok(())
+    Ok(consistent)
 }
 
-fn handle_s_line(line: &Vec<u8>, all_segment: &mut HashMap<usize, usize>) -> Result<(), CmdError> {
-    let mut parts = line.split_str(b"\t");
-    parts.next();
-    let key = gfa::u8_slice_to_usize(parts.next().ok_or(CmdError::EmptyLine)?)?;
-    let value = parts.next().ok_or(CmdError::EmptyLine)?.len();
-    all_segment.insert(key, value);
+fn handle_s_line(line: &[u8], all_segment: &mut HashMap<usize, usize>) -> Result<(), CmdError> {
+    let parser = gfa::GFAParserBuilder::new().get_segments(true).build();
+    if let Some(gfa::GfaEntity::Segment(segment)) = parser.parse_gfa_line(line)? {
+        all_segment.insert(segment.id, segment.length());
+    }
     Ok(())
 }
 
 pub fn convert_1_0(path: String, output: String) -> Result<(), CmdError> {
     log::info!("Converting from 1.1 to 1.0");
-    let file = File::open(&path).map_err(CmdError::FileOpenError)?;
-    let lines = BufReader::new(file).byte_lines();
     let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
     let mut output = BufWriter::new(file);
 
-    for line in lines {
-        let line = line.map_err(CmdError::LineReadError)?;
-        let l = line.as_bytes();
+    binary::for_each_line(&path, |l| {
         if l.starts_with(b"H") {
-            handle_header_line_reverse(&line, &mut output)?;
+            handle_header_line_reverse(l, &mut output)
         } else if l.starts_with(b"W") {
-            handle_w_line(&line, &mut output)?;
+            handle_w_line(l, &mut output)
         } else {
             write_with_error_handling(&mut output, l)?;
-            write_with_error_handling(&mut output, b"\n")?;
+            write_with_error_handling(&mut output, b"\n")
         }
-    }
+    })?;
     output.flush().map_err(|_| CmdError::WriteError)?;
     Ok(())
 }
 
-fn handle_header_line_reverse(
-    line: &Vec<u8>,
-    output: &mut BufWriter<File>,
-) -> Result<(), CmdError> {
-    let new_line = line.replace(b"Z:1.1", b"Z:1.0");
-    output
-        .write_all(new_line.as_bytes())
-        .map_err(|_| CmdError::WriteError)?;
-    output.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
-    Ok(())
+fn handle_header_line_reverse(line: &[u8], output: &mut BufWriter<File>) -> Result<(), CmdError> {
+    write_header_version(line, b"1.0", output)
 }
 
-fn handle_w_line(line: &Vec<u8>, output: &mut BufWriter<File>) -> Result<(), CmdError> {
+fn handle_w_line(line: &[u8], output: &mut BufWriter<File>) -> Result<(), CmdError> {
     let parts: Vec<&[u8]> = line.as_bytes().split(|&b| b == b'\t').collect();
     write_with_error_handling(output, b"P\t")?;
     write_with_error_handling(output, parts[1])?;
@@ -191,11 +253,162 @@ fn handle_w_line(line: &Vec<u8>, output: &mut BufWriter<File>) -> Result<(), Cmd
     write_with_error_handling(output, parts[5])?;
     write_with_error_handling(output, b"\t")?;
     write_with_error_handling(output, &w2p(parts[6]))?;
+
+    let tags = &parts[7.min(parts.len())..];
+    let overlaps = tags.iter().find(|t| t.starts_with(OVERLAP_TAG));
+    match overlaps {
+        Some(overlaps) => {
+            write_with_error_handling(output, b"\t")?;
+            write_with_error_handling(output, &overlaps[OVERLAP_TAG.len()..])?;
+        }
+        None if !tags.is_empty() => write_with_error_handling(output, b"\t*")?,
+        None => {}
+    }
+    for tag in tags.iter().filter(|t| !t.starts_with(OVERLAP_TAG)) {
+        write_with_error_handling(output, b"\t")?;
+        write_with_error_handling(output, tag)?;
+    }
     write_with_error_handling(output, b"\n")?;
 
     Ok(())
 }
 
+pub fn convert_jsonl(path: String, output: String) -> Result<(), CmdError> {
+    log::info!("Converting from GFA to JSON Lines");
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut output = BufWriter::new(file);
+    let parser = gfa::GFAParser::default();
+    let mut lazy = gfa::LazySteps::new(&path);
+
+    binary::for_each_line(&path, |line| {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        if let Some(mut entity) = parser.parse_gfa_line(line)? {
+            lazy.load(&mut entity)?;
+            serde_json::to_writer(&mut output, &jsonl::Record::from(&entity))
+                .map_err(|_| CmdError::WriteError)?;
+            write_with_error_handling(&mut output, b"\n")?;
+        }
+        Ok(())
+    })?;
+    output.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
+pub fn convert_from_jsonl(path: String, output: String) -> Result<(), CmdError> {
+    log::info!("Converting from JSON Lines to GFA");
+    let file = File::open(&path).map_err(CmdError::FileOpenError)?;
+    let lines = BufReader::new(file).byte_lines();
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut output = BufWriter::new(file);
+    let parser = gfa::GFAParser::default();
+
+    let mut checked = false;
+    for line in lines {
+        let line = line.map_err(CmdError::LineReadError)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !checked && !line.trim_start().starts_with(b"{") {
+            return Err(CmdError::InvalidArgument(format!(
+                "{} is neither a binary graph nor JSON Lines",
+                path
+            )));
+        }
+        checked = true;
+        if let Some(entity) = parser.parse_jsonl_line(&line)? {
+            entity.write_line(&mut output)?;
+        }
+    }
+    output.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
+/// Write one GAF record for a haplotype: the whole path aligned end to end
+/// against its own step list, so matches equal the summed segment lengths.
+fn write_gaf_record<W: Write>(
+    output: &mut W,
+    name: &str,
+    steps: gfa::StepIterator,
+    all_segment: &HashMap<usize, usize>,
+) -> Result<(), CmdError> {
+    let steps: Vec<gfa::Step> = steps.collect();
+    if steps.is_empty() {
+        log::warn!("Path {} has no steps, skipped", name);
+        return Ok(());
+    }
+    let mut length = 0;
+    for step in &steps {
+        match all_segment.get(&step.id) {
+            Some(len) => length += len,
+            None => log::warn!("Path {} references unknown segment {}", name, step.id),
+        }
+    }
+    write!(output, "{}\t{}\t0\t{}\t+\t", name, length, length).map_err(|_| CmdError::WriteError)?;
+    write_with_error_handling(output, &gfa::walk_unit(steps))?;
+    writeln!(
+        output,
+        "\t{}\t0\t{}\t{}\t{}\t255\tNM:i:0\tcg:Z:{}M",
+        length, length, length, length, length
+    )
+    .map_err(|_| CmdError::WriteError)
+}
+
+/// Emit every W- and P-line as a GAF alignment of the haplotype to the graph.
+pub fn convert_gaf(path: String, output: String) -> Result<(), CmdError> {
+    log::info!("Converting haplotypes to GAF");
+    let all_segment = segment_lengths(gfa::graph_source(&path))?;
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut output = BufWriter::new(file);
+    let parser = gfa::GFAParserBuilder::new()
+        .get_walks(true)
+        .get_paths(true)
+        .build();
+    let mut lazy = gfa::LazySteps::new(&path);
+
+    binary::for_each_line(&path, |line| {
+        if !line.starts_with(b"W") && !line.starts_with(b"P") {
+            return Ok(());
+        }
+        let mut entity = parser.parse_gfa_line(line)?;
+        if let Some(entity) = entity.as_mut() {
+            lazy.load(entity)?;
+        }
+        match entity {
+            Some(gfa::GfaEntity::Walk(walk)) => {
+                let name = format!("{}:{}-{}", walk.name(), walk.ranges.start, walk.ranges.end);
+                write_gaf_record(&mut output, &name, walk.extract_step(), &all_segment)
+            }
+            Some(gfa::GfaEntity::Path(path)) => {
+                write_gaf_record(&mut output, &path.name(), path.extract_step(), &all_segment)
+            }
+            _ => Ok(()),
+        }
+    })?;
+    output.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
+pub fn convert_binary(path: String, output: String) -> Result<(), CmdError> {
+    log::info!("Converting to binary graph format");
+    let gfa_obj = gfa::GFAParser::default().parse_file(&path)?;
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut output = BufWriter::new(file);
+    binary::write_gfa(&gfa_obj, &mut output)?;
+    Ok(())
+}
+
+pub fn convert_from_binary(path: String, output: String) -> Result<(), CmdError> {
+    log::info!("Converting from binary graph format to GFA");
+    let gfa_obj = binary::read_file(&path, &gfa::GFAParser::default())?;
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut output = BufWriter::new(file);
+    gfa_obj.write_to(&mut output)?;
+    output.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -289,7 +502,7 @@ mod tests {
             L\t11\t+\t12\t-\t0M\n\
             L\t12\t-\t13\t+\t0M\n\
             L\t11\t+\t13\t+\t0M\n\
-            W\tsample\t0\tchr1\t0\t18\t>11<12>13\n";
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\tov:Z:0M,0M\n";
 
         setup_test_file(gfa_data, &input_path);
 
@@ -337,4 +550,212 @@ mod tests {
         let result = read_test_file(&output_path);
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn test_convert_jsonl_round_trip() {
+        let temp_dir = TempDir::new("test_convert_jsonl").unwrap();
+        let input_path = temp_dir.path().join("test_input.gfa");
+        let jsonl_path = temp_dir.path().join("test_output.jsonl");
+        let output_path = temp_dir.path().join("test_output.gfa");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\tLN:i:6\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t12\t-\t13\t+\t0M\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\n\
+            P\tsample#1#chr1\t11+,13+\t0M\n";
+
+        setup_test_file(gfa_data, &input_path);
+
+        convert_jsonl(
+            input_path.to_string_lossy().to_string(),
+            jsonl_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        let jsonl = String::from_utf8(read_test_file(&jsonl_path)).unwrap();
+        assert_eq!(jsonl.lines().count(), 8);
+        assert!(jsonl
+            .lines()
+            .nth(2)
+            .unwrap()
+            .contains("\"tags\":[{\"name\":\"LN\",\"type\":\"i\",\"value\":\"6\"}]"));
+
+        let parsed = gfa::GFAParser::default()
+            .parse_file(&jsonl_path)
+            .expect("Failed to read JSON Lines");
+        assert_eq!(parsed.segments.len(), 3);
+        assert_eq!(parsed.walks[0].unit, b">11<12>13");
+        assert_eq!(
+            parsed.paths[0].extract_node().collect::<Vec<_>>(),
+            vec![11, 13]
+        );
+
+        convert_from_jsonl(
+            jsonl_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        let result = read_test_file(&output_path);
+        assert_eq!(result, gfa_data);
+
+        // a GFA is not mistaken for JSON Lines
+        let err = convert_from_jsonl(
+            input_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        );
+        assert!(matches!(err, Err(CmdError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_convert_binary_round_trip() {
+        let temp_dir = TempDir::new("test_convert_binary").unwrap();
+        let input_path = temp_dir.path().join("test_input.gfa");
+        let binary_path = temp_dir.path().join("test_output.pgb");
+        let output_path = temp_dir.path().join("test_output.gfa");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t12\t-\t13\t+\t0M\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\n";
+
+        setup_test_file(gfa_data, &input_path);
+
+        convert_binary(
+            input_path.to_string_lossy().to_string(),
+            binary_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        assert!(binary::is_binary(&binary_path).unwrap());
+        assert!(!binary::is_binary(&input_path).unwrap());
+
+        let parsed = gfa::GFAParser::default()
+            .parse_file(&binary_path)
+            .expect("Failed to read binary graph");
+        assert_eq!(parsed.get_segment_len().get(&13), Some(&7));
+        // line-based conversions read the binary records as GFA lines
+        convert_jsonl(
+            binary_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        let jsonl = String::from_utf8(read_test_file(&output_path)).unwrap();
+        assert_eq!(jsonl.lines().count(), 7);
+
+        convert_from_binary(
+            binary_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        let result = read_test_file(&output_path);
+        assert_eq!(result, gfa_data);
+    }
+
+    #[test]
+    fn test_convert_1_1_paths_before_segments() {
+        let temp_dir = TempDir::new("test_convert_1_1_case3").unwrap();
+        let input_path = temp_dir.path().join("test_input_1_0_case3.gfa");
+        let output_path = temp_dir.path().join("test_output_1_1_case3.gfa");
+        let gfa_data = b"H\tVN:Z:1.0\n\
+            P\tsample#0#chr1\t11+,12-,13+\t0M,0M\n\
+            P\tsample#1#chr1:10-20\t11+,13+\t0M\n\
+            S\t11\tACCTT\n\
+            S\t12\t*\tLN:i:6\n\
+            S\t13\tCTTGATT\n";
+
+        let expected = b"H\tVN:Z:1.1\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\tov:Z:0M,0M\n\
+            W\tsample\t1\tchr1\t10\t20\t>11>13\tov:Z:0M\n\
+            S\t11\tACCTT\n\
+            S\t12\t*\tLN:i:6\n\
+            S\t13\tCTTGATT\n";
+
+        setup_test_file(gfa_data, &input_path);
+
+        convert_1_1(
+            input_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+
+        let result = read_test_file(&output_path);
+        assert_eq!(result, expected);
+
+        let lengths = segment_lengths(input_path.to_str().unwrap()).unwrap();
+        let mut sink = BufWriter::new(File::create(temp_dir.path().join("sink")).unwrap());
+        assert!(!handle_p_line(b"P\tsample#1#chr1:10-20\t11+,13+", &mut sink, &lengths).unwrap());
+        assert!(handle_p_line(b"P\tsample#1#chr1:10-22\t11+,13+", &mut sink, &lengths).unwrap());
+    }
+
+    #[test]
+    fn test_convert_round_trip_keeps_tags() {
+        let temp_dir = TempDir::new("test_convert_round_trip").unwrap();
+        let input_path = temp_dir.path().join("test_input_1_0.gfa");
+        let middle_path = temp_dir.path().join("test_middle_1_1.gfa");
+        let output_path = temp_dir.path().join("test_output_1_0.gfa");
+        let gfa_data = b"H\tVN:Z:1.0\tRS:Z:ref\tTS:Z:1.0\n\
+            S\t11\tACCTT\tLN:i:5\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            P\tsample#0#chr1:0-18\t11+,12-,13+\t0M,0M\tWT:i:3\n\
+            P\tsample#1#chr1:0-12\t11+,13+\t*\tWT:i:1\n\
+            P\tsample#2#chr1:0-5\t11+\t*\n\
+            P\tref#0#chr1:0-11\t11+,12-\n";
+
+        let expected_middle = b"H\tVN:Z:1.1\tRS:Z:ref\tTS:Z:1.0\n\
+            S\t11\tACCTT\tLN:i:5\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\tWT:i:3\tov:Z:0M,0M\n\
+            W\tsample\t1\tchr1\t0\t12\t>11>13\tWT:i:1\n\
+            W\tsample\t2\tchr1\t0\t5\t>11\tov:Z:*\n\
+            W\tref\t0\tchr1\t0\t11\t>11<12\n";
+
+        setup_test_file(gfa_data, &input_path);
+
+        convert_1_1(
+            input_path.to_string_lossy().to_string(),
+            middle_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        assert_eq!(read_test_file(&middle_path), expected_middle);
+
+        convert_1_0(
+            middle_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+        assert_eq!(read_test_file(&output_path), gfa_data);
+    }
+
+    #[test]
+    fn test_convert_gaf() {
+        let temp_dir = TempDir::new("test_convert_gaf").unwrap();
+        let input_path = temp_dir.path().join("test_input.gfa");
+        let output_path = temp_dir.path().join("test_output.gaf");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            P\tsample#1#chr1\t13+,11-\t0M\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            W\tsample\t0\tchr1\t0\t18\t>11<12>13\n";
+
+        let expected = b"sample#1#chr1\t12\t0\t12\t+\t>13<11\t12\t0\t12\t12\t12\t255\tNM:i:0\tcg:Z:12M\n\
+            sample#0#chr1:0-18\t18\t0\t18\t+\t>11<12>13\t18\t0\t18\t18\t18\t255\tNM:i:0\tcg:Z:18M\n";
+
+        setup_test_file(gfa_data, &input_path);
+
+        convert_gaf(
+            input_path.to_string_lossy().to_string(),
+            output_path.to_string_lossy().to_string(),
+        )
+        .expect("Conversion failed");
+
+        let result = read_test_file(&output_path);
+        assert_eq!(result, expected);
+    }
 }
@@ -0,0 +1,178 @@
+use crate::{error::CmdError, gfa};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+type Edge = (usize, bool, usize, bool);
+
+/// A bidirected edge can be read in either direction, `a+ -> b-` is the
+/// same edge as `b+ -> a-`; keep the smaller of the two.
+fn canonical(from: usize, from_orient: bool, to: usize, to_orient: bool) -> Edge {
+    let forward = (from, from_orient, to, to_orient);
+    let reverse = (to, !to_orient, from, !from_orient);
+    forward.min(reverse)
+}
+
+#[derive(Default)]
+struct EdgeStats {
+    weight: usize,
+    in_gfa: bool,
+}
+
+#[derive(Default)]
+struct NodeStats {
+    traversals: usize,
+    walks: usize,
+}
+
+/// Write the graph as `<prefix>.edges.tsv` and `<prefix>.nodes.tsv`.
+///
+/// Edges are the links plus any adjacency implied by a walk or path but
+/// missing as a link, weighted by the number of walks and paths that
+/// traverse them. Nodes carry their length, the total number of times they
+/// are stepped on and the number of walks and paths that contain them.
+pub fn run(gfa: String, output: String) -> Result<(), CmdError> {
+    let gfa_parser = gfa::GFAParserBuilder::all().build();
+    let gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let mut edges: BTreeMap<Edge, EdgeStats> = BTreeMap::new();
+    for link in &gfa_obj.links {
+        let key = canonical(
+            link.from_segment,
+            link.from_orient,
+            link.to_segment,
+            link.to_orient,
+        );
+        edges.entry(key).or_default().in_gfa = true;
+    }
+    let mut nodes: BTreeMap<usize, NodeStats> = gfa_obj
+        .segments
+        .iter()
+        .map(|s| (s.id, NodeStats::default()))
+        .collect();
+
+    let walks = gfa_obj.walks.iter().map(|w| w.extract_step());
+    let paths = gfa_obj.paths.iter().map(|p| p.extract_step());
+    for steps in walks.chain(paths) {
+        let mut seen_edges: HashSet<Edge> = HashSet::new();
+        let mut seen_nodes: HashSet<usize> = HashSet::new();
+        let mut prev: Option<gfa::Step> = None;
+        for step in steps {
+            let node = nodes.entry(step.id).or_default();
+            node.traversals += 1;
+            if seen_nodes.insert(step.id) {
+                node.walks += 1;
+            }
+            if let Some(p) = prev {
+                let key = canonical(p.id, p.forward, step.id, step.forward);
+                if seen_edges.insert(key) {
+                    edges.entry(key).or_default().weight += 1;
+                }
+            }
+            prev = Some(step);
+        }
+    }
+    let implied = edges.values().filter(|e| !e.in_gfa).count();
+    if implied > 0 {
+        log::warn!(
+            "{} edges are traversed by walks but missing as links",
+            implied
+        );
+    }
+
+    let mut writer = BufWriter::new(
+        File::create(format!("{}.edges.tsv", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(writer, "from\tfrom_orient\tto\tto_orient\tweight\tin_gfa")
+        .map_err(|_| CmdError::WriteError)?;
+    for ((from, from_orient, to, to_orient), stats) in &edges {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            from,
+            gfa::orient_char(*from_orient),
+            to,
+            gfa::orient_char(*to_orient),
+            stats.weight,
+            u8::from(stats.in_gfa)
+        )
+        .map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    let lengths = gfa_obj.get_segment_len();
+    let mut writer = BufWriter::new(
+        File::create(format!("{}.nodes.tsv", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(writer, "node\tlength\ttraversals\twalks").map_err(|_| CmdError::WriteError)?;
+    for (id, stats) in &nodes {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            id,
+            lengths.get(id).copied().unwrap_or(0),
+            stats.traversals,
+            stats.walks
+        )
+        .map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_edges() {
+        let temp_dir = TempDir::new("test_edges").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_prefix = temp_dir.path().join("graph");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tA\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t12\t-\t13\t+\t0M\n\
+            L\t13\t+\t14\t+\t0M\n\
+            W\tsample1\t0\tchr1\t0\t18\t>11<12>13\n\
+            W\tsample2\t0\tchr1\t0\t12\t>11>13>11>13\n\
+            P\tsample3#0#chr1\t13-,12+,11-\t*\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let prefix = output_prefix.to_str().unwrap().to_string();
+        run(gfa_file_path.to_str().unwrap().to_string(), prefix.clone()).unwrap();
+
+        let edges = std::fs::read_to_string(format!("{}.edges.tsv", prefix)).unwrap();
+        assert_eq!(
+            edges,
+            "from\tfrom_orient\tto\tto_orient\tweight\tin_gfa\n\
+             11\t-\t13\t-\t1\t0\n\
+             11\t+\t12\t-\t2\t1\n\
+             11\t+\t13\t+\t1\t0\n\
+             12\t-\t13\t+\t2\t1\n\
+             13\t+\t14\t+\t0\t1\n"
+        );
+        let nodes = std::fs::read_to_string(format!("{}.nodes.tsv", prefix)).unwrap();
+        assert_eq!(
+            nodes,
+            "node\tlength\ttraversals\twalks\n\
+             11\t5\t4\t3\n\
+             12\t6\t2\t2\n\
+             13\t7\t4\t3\n\
+             14\t1\t0\t0\n"
+        );
+    }
+}
@@ -0,0 +1,331 @@
+use super::pav;
+use crate::error::CmdError;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+/// Quantiles reported for permutations.
+const QUANTILES: [f64; 3] = [0.05, 0.5, 0.95];
+
+/// SplitMix64, enough to shuffle sample orders reproducibly from a seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+/// Pan and core size for k = 1..=N samples, a mean and optional quantiles.
+struct Curve {
+    pan: Vec<f64>,
+    core: Vec<f64>,
+    pan_quantiles: Option<Vec<Vec<u64>>>,
+    core_quantiles: Option<Vec<Vec<u64>>>,
+}
+
+/// Expected pan and core sizes over all subsets of k samples, from the
+/// weight of nodes found in f samples, `histogram[f]`.
+fn exact(histogram: &[u64]) -> Curve {
+    let n = histogram.len() - 1;
+    let mut pan = Vec::with_capacity(n);
+    let mut core = Vec::with_capacity(n);
+    for k in 1..=n {
+        let (mut p, mut c) = (0.0, 0.0);
+        for (f, &weight) in histogram.iter().enumerate().skip(1) {
+            // C(n - f, k) / C(n, k) and C(f, k) / C(n, k)
+            let (mut missed, mut kept) = (1.0, 1.0);
+            for i in 0..k {
+                missed *= (n - f).saturating_sub(i) as f64 / (n - i) as f64;
+                kept *= f.saturating_sub(i) as f64 / (n - i) as f64;
+            }
+            p += weight as f64 * (1.0 - missed);
+            c += weight as f64 * kept;
+        }
+        pan.push(p);
+        core.push(c);
+    }
+    Curve {
+        pan,
+        core,
+        pan_quantiles: None,
+        core_quantiles: None,
+    }
+}
+
+/// Pan and core sizes along `permutations` random sample orders. Each node
+/// is a bitset of the columns it is present in.
+fn permuted(
+    presence: &[u64],
+    weights: &[u64],
+    width: usize,
+    permutations: usize,
+    seed: u64,
+) -> Curve {
+    let words = width.div_ceil(64);
+    let mut rng = Rng(seed);
+    let mut order: Vec<usize> = (0..width).collect();
+    let mut pan_runs: Vec<Vec<u64>> = vec![Vec::with_capacity(permutations); width];
+    let mut core_runs: Vec<Vec<u64>> = vec![Vec::with_capacity(permutations); width];
+    for _ in 0..permutations {
+        rng.shuffle(&mut order);
+        // rank of the first sample a node is found in, and missing from
+        let mut first_present = vec![0u64; width + 1];
+        let mut first_absent = vec![0u64; width + 1];
+        for (bits, &weight) in presence.chunks(words).zip(weights) {
+            let (mut present, mut absent) = (None, None);
+            for (rank, &col) in order.iter().enumerate() {
+                let found = bits[col / 64] >> (col % 64) & 1 == 1;
+                match found {
+                    true if present.is_none() => present = Some(rank),
+                    false if absent.is_none() => absent = Some(rank),
+                    _ => {}
+                }
+                if present.is_some() && absent.is_some() {
+                    break;
+                }
+            }
+            first_present[present.unwrap_or(width)] += weight;
+            first_absent[absent.unwrap_or(width)] += weight;
+        }
+        let mut pan = 0;
+        let mut core: u64 = first_absent.iter().skip(1).sum();
+        for k in 1..=width {
+            pan += first_present[k - 1];
+            pan_runs[k - 1].push(pan);
+            core_runs[k - 1].push(core);
+            core -= first_absent[k];
+        }
+    }
+    let mean = |runs: &Vec<Vec<u64>>| -> Vec<f64> {
+        runs.iter()
+            .map(|r| r.iter().sum::<u64>() as f64 / r.len().max(1) as f64)
+            .collect()
+    };
+    let quantiles = |runs: &mut Vec<Vec<u64>>| -> Vec<Vec<u64>> {
+        runs.iter_mut()
+            .map(|r| {
+                r.sort_unstable();
+                QUANTILES
+                    .iter()
+                    .map(|q| r[((r.len() - 1) as f64 * q).round() as usize])
+                    .collect()
+            })
+            .collect()
+    };
+    Curve {
+        pan: mean(&pan_runs),
+        core: mean(&core_runs),
+        pan_quantiles: Some(quantiles(&mut pan_runs)),
+        core_quantiles: Some(quantiles(&mut core_runs)),
+    }
+}
+
+/// Least-squares fit of Heaps' law, new(k) = kappa * k^-alpha, to the
+/// growth of the mean pan size.
+fn heaps_fit(pan: &[f64]) -> Option<(f64, f64)> {
+    let points: Vec<(f64, f64)> = (1..pan.len())
+        .map(|i| ((i + 1) as f64, pan[i] - pan[i - 1]))
+        .filter(|&(_, new)| new > 0.0)
+        .map(|(k, new)| (k.ln(), new.ln()))
+        .collect();
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxy: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let sxx: f64 = points.iter().map(|p| (p.0 - mean_x).powi(2)).sum();
+    let slope = sxy / sxx;
+    Some(((mean_y - slope * mean_x).exp(), -slope))
+}
+
+/// Write pangenome growth curves: the mean, and with `permutation` the
+/// 5%, 50% and 95% quantiles, of the number of nodes, or bp with `bp`, in
+/// the pan and core genome of k = 1..N samples.
+///
+/// `method` is `exact`, expectations over all subsets from the node
+/// frequency histogram, or `permutation`, `permutations` random orders of
+/// the samples drawn from `seed`. The Heaps' law fit of the new nodes per
+/// added sample goes to `<output>.heaps`; alpha > 1 means a closed
+/// pangenome.
+pub fn run(
+    gfa: String,
+    output: String,
+    columns: String,
+    method: String,
+    permutations: usize,
+    seed: u64,
+    bp: bool,
+) -> Result<(), CmdError> {
+    if !["exact", "permutation"].contains(&method.as_str()) {
+        return Err(CmdError::InvalidArgument(format!(
+            "unknown method {}, expected exact or permutation",
+            method
+        )));
+    }
+    let (gfa_obj, lengths) = pav::load_graph(&gfa)?;
+    let columns = pav::Columns::new(&gfa_obj.walks, &columns, None)?;
+    let width = columns.width();
+    if width == 0 {
+        return Err(CmdError::InvalidArgument(format!("{} has no walks", gfa)));
+    }
+
+    let words = width.div_ceil(64);
+    let keep_presence = method == "permutation";
+    let mut histogram = vec![0u64; width + 1];
+    let mut presence: Vec<u64> = Vec::new();
+    let mut weights: Vec<u64> = Vec::new();
+    columns.count_rows(&pav::graph_nodes(&gfa_obj, &lengths), |id, row| {
+        let weight = match bp {
+            true => lengths.get(&id).copied().unwrap_or(0) as u64,
+            false => 1,
+        };
+        let frequency = row.iter().filter(|c| c.total() > 0).count();
+        histogram[frequency] += weight;
+        if keep_presence && frequency > 0 {
+            let mut bits = vec![0u64; words];
+            for (col, cell) in row.iter().enumerate() {
+                if cell.total() > 0 {
+                    bits[col / 64] |= 1 << (col % 64);
+                }
+            }
+            presence.extend(bits);
+            weights.push(weight);
+        }
+        Ok(())
+    })?;
+    log::debug!("Node frequency histogram: {:?}", histogram);
+
+    let curve = match keep_presence {
+        true => permuted(&presence, &weights, width, permutations.max(1), seed),
+        false => exact(&histogram),
+    };
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    writeln!(
+        writer,
+        "k\tpan_mean\tpan_q05\tpan_q50\tpan_q95\tcore_mean\tcore_q05\tcore_q50\tcore_q95"
+    )
+    .map_err(|_| CmdError::WriteError)?;
+    let cells = |quantiles: &Option<Vec<Vec<u64>>>, k: usize| -> String {
+        match quantiles {
+            Some(q) => q[k]
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join("\t"),
+            None => vec!["NA"; QUANTILES.len()].join("\t"),
+        }
+    };
+    for k in 0..width {
+        writeln!(
+            writer,
+            "{}\t{:.2}\t{}\t{:.2}\t{}",
+            k + 1,
+            curve.pan[k],
+            cells(&curve.pan_quantiles, k),
+            curve.core[k],
+            cells(&curve.core_quantiles, k)
+        )
+        .map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    let mut writer = BufWriter::new(
+        File::create(format!("{}.heaps", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(writer, "kappa\talpha\tpangenome").map_err(|_| CmdError::WriteError)?;
+    match heaps_fit(&curve.pan) {
+        Some((kappa, alpha)) => {
+            let openness = if alpha > 1.0 { "closed" } else { "open" };
+            writeln!(writer, "{:.4}\t{:.4}\t{}", kappa, alpha, openness)
+                .map_err(|_| CmdError::WriteError)?;
+        }
+        None => {
+            log::warn!("Too few samples adding new nodes to fit Heaps' law");
+            writeln!(writer, "NA\tNA\tNA").map_err(|_| CmdError::WriteError)?;
+        }
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_growth() {
+        let temp_dir = TempDir::new("test_growth").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("growth.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tAA\n\
+            W\tsample1\t0\tchr1\t0\t18\t>11>12>13\n\
+            W\tsample2\t0\tchr1\t0\t11\t>11>12\n\
+            W\tsample3\t0\tchr1\t0\t7\t>11>14\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        let growth = |method: &str| {
+            run(
+                gfa_file_path.to_str().unwrap().to_string(),
+                output.clone(),
+                "sample".to_string(),
+                method.to_string(),
+                20,
+                7,
+                false,
+            )
+            .unwrap();
+            std::fs::read_to_string(&output).unwrap()
+        };
+
+        assert_eq!(
+            growth("exact"),
+            "k\tpan_mean\tpan_q05\tpan_q50\tpan_q95\tcore_mean\tcore_q05\tcore_q50\tcore_q95\n\
+             1\t2.33\tNA\tNA\tNA\t2.33\tNA\tNA\tNA\n\
+             2\t3.33\tNA\tNA\tNA\t1.33\tNA\tNA\tNA\n\
+             3\t4.00\tNA\tNA\tNA\t1.00\tNA\tNA\tNA\n"
+        );
+        let heaps = std::fs::read_to_string(format!("{}.heaps", output)).unwrap();
+        assert!(heaps.starts_with("kappa\talpha\tpangenome\n2.0000\t1.0000\t"));
+
+        let permuted = growth("permutation");
+        let rows: Vec<Vec<&str>> = permuted
+            .lines()
+            .skip(1)
+            .map(|l| l.split('\t').collect())
+            .collect();
+        assert_eq!(rows[2], ["3", "4.00", "4", "4", "4", "1.00", "1", "1", "1"]);
+        for row in &rows[..2] {
+            let pan: f64 = row[1].parse().unwrap();
+            let core: f64 = row[5].parse().unwrap();
+            assert!(core <= pan && pan <= 4.0 && core >= 1.0);
+        }
+    }
+}
@@ -1,23 +1,40 @@
+use crate::binary;
 use crate::error::CmdError;
-use bstr::io::BufReadExt;
+use crate::gfa::OFFSET_TAG;
 use bstr::ByteSlice;
 use std::fs::File;
-use std::io::{BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
+/// Write `<gfa>.simple` with the P/W lines of `gfa`, their steps replaced by
+/// `*` and the byte offset of the original line stored in a `bo:i` tag.
+/// A binary graph has no lines to point at and is rejected.
 pub fn build(gfa: &str) -> Result<(), CmdError> {
+    binary::require_text(gfa)?;
+
     // Create output file
     let mut gfa_simple =
         BufWriter::new(File::create(format!("{}.simple", gfa)).map_err(CmdError::FileOpenError)?);
 
     // Open input file
     let file = File::open(gfa).map_err(CmdError::FileOpenError)?;
-    let reader = BufReader::new(file);
+    let mut reader = BufReader::new(file);
 
     // Process each line in the input file
-    for line in reader.byte_lines() {
-        let line = line.map_err(CmdError::LineReadError)?;
+    let mut offset = 0;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(CmdError::LineReadError)?;
+        if read == 0 {
+            break;
+        }
+        let line_offset = offset;
+        offset += read;
+        let line = buf.trim_end();
 
-        match line.get(0) {
+        match line.first() {
             Some(&b'P') | Some(&b'W') => {
                 // Split the line into parts by tabs
                 let mut parts: Vec<&[u8]> = line.split(|&b| b == b'\t').collect();
@@ -29,8 +46,7 @@ pub fn build(gfa: &str) -> Result<(), CmdError> {
                 gfa_simple
                     .write_all(joined_line.as_bytes())
                     .map_err(|_| CmdError::WriteError)?;
-                gfa_simple
-                    .write_all(b"\n")
+                writeln!(gfa_simple, "\t{}:i:{}", OFFSET_TAG, line_offset)
                     .map_err(|_| CmdError::WriteError)?;
             }
             // Skip lines that don't start with 'P' or 'W'
@@ -43,3 +59,60 @@ pub fn build(gfa: &str) -> Result<(), CmdError> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfa;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_index_lazy_steps() {
+        let temp_dir = TempDir::new("test_index").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            P\tsample3#0#chr1\t11+,12-\t*\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let gfa_file = gfa_file_path.to_str().unwrap().to_string();
+        build(&gfa_file).unwrap();
+        let index = format!("{}.simple", gfa_file);
+        assert_eq!(
+            std::fs::read_to_string(&index).unwrap(),
+            "P\tsample3#0#chr1\t*\t*\tbo:i:34\n\
+             W\tsample1\t0\tchr1\t0\t11\t*\tbo:i:61\n"
+        );
+
+        let parser = gfa::GFAParserBuilder::all().build();
+        let mut walk = match parser
+            .parse_gfa_line(b"W\tsample1\t0\tchr1\t0\t11\t*\tbo:i:61")
+            .unwrap()
+        {
+            Some(gfa::GfaEntity::Walk(walk)) => walk,
+            _ => unreachable!(),
+        };
+        assert!(walk.is_lazy());
+        assert_eq!(walk.extract_node().count(), 0);
+        let mut source = gfa::StepSource::for_index(&index).unwrap();
+        walk.load_steps(&mut source).unwrap();
+        assert_eq!(walk.unit, b">11<12");
+
+        // parsing the index loads the steps of every record
+        let gfa_obj = parser.parse_file(&index).unwrap();
+        assert!(!gfa_obj.walks[0].is_lazy());
+        assert_eq!(gfa_obj.walks[0].unit, b">11<12");
+        assert!(gfa_obj.walks[0].tags.is_empty());
+        assert_eq!(
+            gfa_obj.paths[0].extract_node().collect::<Vec<_>>(),
+            [11, 12]
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use crate::gfa::GfaWritable;
+use crate::{error::CmdError, gfa};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use tempdir::TempDir;
+
+use log;
+
+/// One W-line, or P-line, of a haplotype contig.
+struct Fragment {
+    start: usize,
+    end: usize,
+    steps: Vec<gfa::Step>,
+    tags: Vec<gfa::Tag>,
+}
+
+type Key = (String, String, String);
+
+/// Group walks and paths by sample, haptype and contig, ordered by start.
+fn fragments(gfa_obj: &gfa::GFA) -> BTreeMap<Key, Vec<Fragment>> {
+    let lengths = gfa_obj.get_segment_len();
+    let mut groups: BTreeMap<Key, Vec<Fragment>> = BTreeMap::new();
+    for walk in &gfa_obj.walks {
+        groups
+            .entry((
+                walk.sample.clone(),
+                walk.haptype.clone(),
+                walk.chroms.clone(),
+            ))
+            .or_default()
+            .push(Fragment {
+                start: walk.ranges.start,
+                end: walk.ranges.end,
+                steps: walk.extract_step().collect(),
+                tags: walk.tags.clone(),
+            });
+    }
+    for path in &gfa_obj.paths {
+        let ranges = path.ranges.unwrap_or_else(|| gfa::Range {
+            start: 0,
+            end: path.extract_node().filter_map(|n| lengths.get(&n)).sum(),
+        });
+        groups
+            .entry((
+                path.sample.clone(),
+                path.haptype.to_string(),
+                path.chroms.clone(),
+            ))
+            .or_default()
+            .push(Fragment {
+                start: ranges.start,
+                end: ranges.end,
+                steps: path.extract_step().collect(),
+                tags: path.tags.clone(),
+            });
+    }
+    for group in groups.values_mut() {
+        group.sort_by_key(|f| (f.start, f.end));
+    }
+    groups
+}
+
+/// Distance from the end of the previous fragment to the start of this one,
+/// negative when they overlap.
+fn gap(prev: &Fragment, next: &Fragment) -> i64 {
+    next.start as i64 - prev.end as i64
+}
+
+fn write_report<W: Write>(
+    writer: &mut W,
+    groups: &BTreeMap<Key, Vec<Fragment>>,
+) -> Result<(), CmdError> {
+    writeln!(writer, "sample\thaptype\tchrom\tstart\tend\tgap")
+        .map_err(|_| CmdError::WriteError)?;
+    for ((sample, haptype, chrom), group) in groups {
+        for (i, fragment) in group.iter().enumerate() {
+            let gap = match i {
+                0 => ".".to_string(),
+                _ => gap(&group[i - 1], fragment).to_string(),
+            };
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                sample, haptype, chrom, fragment.start, fragment.end, gap
+            )
+            .map_err(|_| CmdError::WriteError)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_summary<W: Write>(
+    writer: &mut W,
+    groups: &BTreeMap<Key, Vec<Fragment>>,
+) -> Result<(), CmdError> {
+    writeln!(
+        writer,
+        "sample\thaptype\tchrom\tfragments\tstart\tend\tgaps\tgap_bp\toverlaps\toverlap_bp"
+    )
+    .map_err(|_| CmdError::WriteError)?;
+    for ((sample, haptype, chrom), group) in groups {
+        let (mut gaps, mut gap_bp, mut overlaps, mut overlap_bp) = (0, 0, 0, 0);
+        for pair in group.windows(2) {
+            let d = gap(&pair[0], &pair[1]);
+            if d > 0 {
+                gaps += 1;
+                gap_bp += d;
+            } else if d < 0 {
+                overlaps += 1;
+                overlap_bp -= d;
+            }
+        }
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            sample,
+            haptype,
+            chrom,
+            group.len(),
+            group[0].start,
+            group.iter().map(|f| f.end).max().unwrap_or_default(),
+            gaps,
+            gap_bp,
+            overlaps,
+            overlap_bp
+        )
+        .map_err(|_| CmdError::WriteError)?;
+    }
+    Ok(())
+}
+
+/// Add the tags of a joined fragment to those of its run, keeping the first
+/// value of a tag both carry; returns how many such values differed.
+fn merge_tags(kept: &mut Vec<gfa::Tag>, tags: Vec<gfa::Tag>) -> usize {
+    let mut conflicts = 0;
+    for tag in tags {
+        match kept.iter().find(|t| t.name == tag.name) {
+            Some(t) if *t != tag => conflicts += 1,
+            Some(_) => {}
+            None => kept.push(tag),
+        }
+    }
+    conflicts
+}
+
+/// Write the graph with the fragments of every haplotype contig joined into
+/// one W-line wherever a fragment starts exactly where the previous one
+/// ends; fragments separated by a gap or an overlap stay separate W-lines.
+/// A joined W-line carries the tags of all its fragments.
+fn write_merged<W: Write>(
+    writer: &mut W,
+    gfa_obj: &gfa::GFA,
+    groups: BTreeMap<Key, Vec<Fragment>>,
+) -> Result<(), CmdError> {
+    if !gfa_obj.headers.version.is_empty() {
+        gfa::Header::from_tags("1.1".to_string(), gfa_obj.headers.tags.clone())
+            .write_line(writer)?;
+    }
+    for segment in &gfa_obj.segments {
+        segment.write_line(writer)?;
+    }
+    for link in &gfa_obj.links {
+        link.write_line(writer)?;
+    }
+    let mut fragmented = 0;
+    for ((sample, haptype, chroms), group) in groups {
+        let mut runs: Vec<Fragment> = Vec::new();
+        for fragment in group {
+            match runs.last_mut() {
+                Some(run) if run.end == fragment.start => {
+                    run.end = fragment.end;
+                    run.steps.extend(fragment.steps);
+                    let conflicts = merge_tags(&mut run.tags, fragment.tags);
+                    if conflicts > 0 {
+                        log::warn!(
+                            "{} tags of {}#{}#{} differ between joined fragments, kept the first",
+                            conflicts,
+                            sample,
+                            haptype,
+                            chroms
+                        );
+                    }
+                }
+                _ => runs.push(fragment),
+            }
+        }
+        if runs.len() > 1 {
+            fragmented += 1;
+        }
+        for run in runs {
+            let walk = gfa::Walk {
+                sample: sample.clone(),
+                haptype: haptype.clone(),
+                chroms: chroms.clone(),
+                ranges: gfa::Range {
+                    start: run.start,
+                    end: run.end,
+                },
+                unit: gfa::walk_unit(run.steps),
+                tags: run.tags,
+            };
+            walk.write_line(writer)?;
+        }
+    }
+    if fragmented > 0 {
+        log::warn!(
+            "{} haplotype contigs have gaps or overlaps and are left in fragments",
+            fragmented
+        );
+    }
+    Ok(())
+}
+
+/// Write the joined graph of `gfa` to a temporary directory, for commands
+/// that analyse joined contigs instead of their fragments, and return the
+/// directory with the path of the graph in it.
+pub fn joined_graph(gfa: &str) -> Result<(TempDir, String), CmdError> {
+    let dir = TempDir::new("pantools_join").map_err(|_| CmdError::CreateFileError)?;
+    let path = dir.path().join("joined.gfa").to_string_lossy().into_owned();
+    run(gfa.to_string(), path.clone(), "gfa".to_string())?;
+    Ok((dir, path))
+}
+
+/// Group the walks and paths of each `sample#haptype#contig` and order them
+/// by start coordinate.
+///
+/// `format` is `report` (one row per fragment with the gap to the previous
+/// one, negative for an overlap), `summary` (one row per contig) or `gfa`
+/// (the graph with the abutting fragments of each contig joined into one
+/// W-line). `pav` and `convert` read that graph with `--join`.
+pub fn run(gfa: String, output: String, format: String) -> Result<(), CmdError> {
+    let gfa_parser = gfa::GFAParserBuilder::all().build();
+    let gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let groups = fragments(&gfa_obj);
+    let fragmented = groups.values().filter(|g| g.len() > 1).count();
+    log::info!(
+        "{} of {} haplotype contigs are fragmented",
+        fragmented,
+        groups.len()
+    );
+    let overlapping = groups
+        .values()
+        .filter(|g| g.windows(2).any(|p| gap(&p[0], &p[1]) < 0))
+        .count();
+    if overlapping > 0 {
+        log::warn!(
+            "{} haplotype contigs have overlapping fragments",
+            overlapping
+        );
+    }
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    match format.as_str() {
+        "report" => write_report(&mut writer, &groups)?,
+        "summary" => write_summary(&mut writer, &groups)?,
+        "gfa" => write_merged(&mut writer, &gfa_obj, groups)?,
+        _ => {
+            return Err(CmdError::InvalidArgument(format!(
+                "unknown format {}, expected report, summary or gfa",
+                format
+            )))
+        }
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_join() {
+        let temp_dir = TempDir::new("test_join").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            W\tsample1\t0\tchr1\t20\t27\t>13\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+            W\tsample1\t0\tchr1\t25\t30\t>11\n\
+            W\tsample2\t0\tchr1\t0\t18\t>11<12>13\n\
+            W\tsample3\t0\tchr1\t5\t11\t<12\tRC:i:2\tXS:Z:b\n\
+            W\tsample3\t0\tchr1\t0\t5\t>11\tXS:Z:a\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let gfa_file = gfa_file_path.to_str().unwrap().to_string();
+        let output = output_file_path.to_str().unwrap().to_string();
+        let join = |format: &str| {
+            run(gfa_file.clone(), output.clone(), format.to_string()).unwrap();
+            std::fs::read_to_string(&output).unwrap()
+        };
+
+        assert_eq!(
+            join("report"),
+            "sample\thaptype\tchrom\tstart\tend\tgap\n\
+             sample1\t0\tchr1\t0\t11\t.\n\
+             sample1\t0\tchr1\t20\t27\t9\n\
+             sample1\t0\tchr1\t25\t30\t-2\n\
+             sample2\t0\tchr1\t0\t18\t.\n\
+             sample3\t0\tchr1\t0\t5\t.\n\
+             sample3\t0\tchr1\t5\t11\t0\n"
+        );
+        assert_eq!(
+            join("summary"),
+            "sample\thaptype\tchrom\tfragments\tstart\tend\tgaps\tgap_bp\toverlaps\toverlap_bp\n\
+             sample1\t0\tchr1\t3\t0\t30\t1\t9\t1\t2\n\
+             sample2\t0\tchr1\t1\t0\t18\t0\t0\t0\t0\n\
+             sample3\t0\tchr1\t2\t0\t11\t0\t0\t0\t0\n"
+        );
+        assert_eq!(
+            join("gfa"),
+            "H\tVN:Z:1.1\n\
+             S\t11\tACCTT\n\
+             S\t12\tTCAAGG\n\
+             S\t13\tCTTGATT\n\
+             L\t11\t+\t12\t-\t0M\n\
+             W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+             W\tsample1\t0\tchr1\t20\t27\t>13\n\
+             W\tsample1\t0\tchr1\t25\t30\t>11\n\
+             W\tsample2\t0\tchr1\t0\t18\t>11<12>13\n\
+             W\tsample3\t0\tchr1\t0\t11\t>11<12\tXS:Z:a\tRC:i:2\n"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use crate::error::CmdError;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+/// Size of the `.npy` header, magic and padding included; a multiple of 64
+/// with room for any shape.
+const NPY_HEADER: usize = 128;
+
+/// Matrices written a row at a time whose header, which holds the number
+/// of rows, is rewritten once all rows are known.
+pub(crate) trait MatrixWriter {
+    fn header(&self) -> Vec<u8>;
+    fn writer(&mut self) -> &mut BufWriter<File>;
+    fn write_row(&mut self, values: &[u64]) -> Result<(), CmdError>;
+
+    fn finish(mut self: Box<Self>) -> Result<(), CmdError> {
+        let header = self.header();
+        let writer = self.writer();
+        writer
+            .seek(SeekFrom::Start(0))
+            .map_err(|_| CmdError::WriteError)?;
+        writer
+            .write_all(&header)
+            .map_err(|_| CmdError::WriteError)?;
+        writer.flush().map_err(|_| CmdError::WriteError)
+    }
+}
+
+/// Create a writer of `cols` columns: `mtx` (Matrix Market coordinate),
+/// `bits` (bit-packed presence, uncompressed) or `npy` (NumPy, `<u8` when `wide`,
+/// `<u4` otherwise).
+pub(crate) fn create(
+    format: &str,
+    path: &str,
+    cols: usize,
+    wide: bool,
+) -> Result<Box<dyn MatrixWriter>, CmdError> {
+    let writer = BufWriter::new(File::create(path).map_err(|_| CmdError::CreateFileError)?);
+    let mut matrix: Box<dyn MatrixWriter> = match format {
+        "mtx" => Box::new(MtxWriter {
+            writer,
+            rows: 0,
+            cols,
+            nnz: 0,
+        }),
+        "bits" => Box::new(BitsWriter {
+            writer,
+            rows: 0,
+            cols,
+        }),
+        "npy" => Box::new(NpyWriter {
+            writer,
+            rows: 0,
+            cols,
+            wide,
+        }),
+        _ => {
+            return Err(CmdError::InvalidArgument(format!(
+                "unknown format {}, expected tsv, mtx, bits or npy",
+                format
+            )))
+        }
+    };
+    // reserve the header, it is written again by finish
+    let header = matrix.header();
+    matrix
+        .writer()
+        .write_all(&header)
+        .map_err(|_| CmdError::WriteError)?;
+    Ok(matrix)
+}
+
+/// Write one label per line.
+pub(crate) fn write_labels<T: std::fmt::Display>(path: &str, labels: &[T]) -> Result<(), CmdError> {
+    let mut writer = BufWriter::new(File::create(path).map_err(|_| CmdError::CreateFileError)?);
+    for label in labels {
+        writeln!(writer, "{}", label).map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)
+}
+
+/// Matrix Market coordinate format with 1-based indices of non-zero cells.
+struct MtxWriter {
+    writer: BufWriter<File>,
+    rows: usize,
+    cols: usize,
+    nnz: usize,
+}
+
+impl MatrixWriter for MtxWriter {
+    fn header(&self) -> Vec<u8> {
+        format!(
+            "%%MatrixMarket matrix coordinate integer general\n{:<20} {:<20} {:<20}\n",
+            self.rows, self.cols, self.nnz
+        )
+        .into_bytes()
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        &mut self.writer
+    }
+
+    fn write_row(&mut self, values: &[u64]) -> Result<(), CmdError> {
+        self.rows += 1;
+        for (col, &v) in values.iter().enumerate().filter(|(_, &v)| v > 0) {
+            writeln!(self.writer, "{} {} {}", self.rows, col + 1, v)
+                .map_err(|_| CmdError::WriteError)?;
+            self.nnz += 1;
+        }
+        Ok(())
+    }
+}
+
+/// `PAVB`, the number of rows and columns as u64 LE, then one bit per cell,
+/// set when it is non-zero, rows padded to whole bytes and bits in
+/// little-endian order.
+struct BitsWriter {
+    writer: BufWriter<File>,
+    rows: usize,
+    cols: usize,
+}
+
+impl MatrixWriter for BitsWriter {
+    fn header(&self) -> Vec<u8> {
+        let mut header = b"PAVB".to_vec();
+        header.extend_from_slice(&(self.rows as u64).to_le_bytes());
+        header.extend_from_slice(&(self.cols as u64).to_le_bytes());
+        header
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        &mut self.writer
+    }
+
+    fn write_row(&mut self, values: &[u64]) -> Result<(), CmdError> {
+        self.rows += 1;
+        let mut bytes = vec![0u8; self.cols.div_ceil(8)];
+        for (col, _) in values.iter().enumerate().filter(|(_, &v)| v > 0) {
+            bytes[col / 8] |= 1 << (col % 8);
+        }
+        self.writer
+            .write_all(&bytes)
+            .map_err(|_| CmdError::WriteError)
+    }
+}
+
+/// NumPy `.npy` version 1.0, a C-order array of unsigned integers.
+struct NpyWriter {
+    writer: BufWriter<File>,
+    rows: usize,
+    cols: usize,
+    wide: bool,
+}
+
+impl MatrixWriter for NpyWriter {
+    fn header(&self) -> Vec<u8> {
+        let descr = if self.wide { "<u8" } else { "<u4" };
+        let dict = format!(
+            "{{'descr': '{}', 'fortran_order': False, 'shape': ({}, {}), }}",
+            descr, self.rows, self.cols
+        );
+        let mut header = b"\x93NUMPY\x01\x00".to_vec();
+        header.extend_from_slice(&((NPY_HEADER - 10) as u16).to_le_bytes());
+        header.extend_from_slice(dict.as_bytes());
+        header.resize(NPY_HEADER - 1, b' ');
+        header.push(b'\n');
+        header
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        &mut self.writer
+    }
+
+    fn write_row(&mut self, values: &[u64]) -> Result<(), CmdError> {
+        self.rows += 1;
+        for &v in values {
+            match self.wide {
+                true => self.writer.write_all(&v.to_le_bytes()),
+                false => self.writer.write_all(&(v as u32).to_le_bytes()),
+            }
+            .map_err(|_| CmdError::WriteError)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use super::convert;
+use crate::gfa::{GfaEntity, GfaWritable};
+use crate::{binary, error::CmdError, gfa};
+use bstr::ByteSlice;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+/// What the first pass learns about one input graph.
+struct InputSummary {
+    /// Largest segment ID on any S-, L-, W- or P-line.
+    max_id: Option<usize>,
+    lengths: HashMap<usize, usize>,
+}
+
+fn for_each_entity(
+    path: &str,
+    parser: &gfa::GFAParser,
+    mut f: impl FnMut(GfaEntity) -> Result<(), CmdError>,
+) -> Result<(), CmdError> {
+    let mut lazy = gfa::LazySteps::new(path);
+    binary::for_each_line(path, |line| {
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        match parser.parse_gfa_line(line)? {
+            Some(mut entity) => {
+                lazy.load(&mut entity)?;
+                f(entity)
+            }
+            None => Ok(()),
+        }
+    })
+}
+
+fn offset_steps(steps: gfa::StepIterator, offset: usize) -> Vec<gfa::Step> {
+    steps
+        .map(|s| gfa::Step {
+            id: s.id + offset,
+            forward: s.forward,
+        })
+        .collect()
+}
+
+/// Merge several GFA files into a single GFA 1.1.
+///
+/// Segment IDs of every input are offset past the largest ID of the inputs
+/// before it, and the offsets are written to `<output>.offsets`. With
+/// `shared`, IDs are kept and segments with the same ID are written once,
+/// which requires their sequences to be identical. P-lines become W-lines,
+/// their overlaps kept in an `ov:Z:` tag as by `convert --to 1.1`.
+pub fn run(gfa: Vec<String>, output: String, shared: bool) -> Result<(), CmdError> {
+    let parser = gfa::GFAParser::default();
+    let mut summaries = Vec::with_capacity(gfa.len());
+    let mut tags: Vec<gfa::Tag> = Vec::new();
+    let mut sequences: HashMap<usize, Vec<u8>> = HashMap::new();
+    for input in &gfa {
+        let mut summary = InputSummary {
+            max_id: None,
+            lengths: HashMap::new(),
+        };
+        let mut see = |id: usize| summary.max_id = summary.max_id.max(Some(id));
+        for_each_entity(input, &parser, |entity| {
+            match entity {
+                GfaEntity::Header(header) => {
+                    for tag in header.tags {
+                        match tags.iter().find(|t| t.name == tag.name) {
+                            Some(kept) if *kept != tag => log::warn!(
+                                "{} in {} conflicts with {}, keeping the latter",
+                                tag,
+                                input,
+                                kept
+                            ),
+                            Some(_) => {}
+                            None => tags.push(tag),
+                        }
+                    }
+                }
+                GfaEntity::Link(link) => {
+                    see(link.from_segment);
+                    see(link.to_segment);
+                }
+                GfaEntity::Walk(walk) => walk.extract_node().for_each(&mut see),
+                GfaEntity::Path(path) => path.extract_node().for_each(&mut see),
+                GfaEntity::Segment(segment) => {
+                    see(segment.id);
+                    summary.lengths.insert(segment.id, segment.length());
+                    if shared {
+                        match sequences.get(&segment.id) {
+                            Some(seq) if *seq != segment.sequence => {
+                                return Err(CmdError::InvalidArgument(format!(
+                                    "segment {} in {} differs from an earlier input",
+                                    segment.id, input
+                                )));
+                            }
+                            Some(_) => {}
+                            None => {
+                                sequences.insert(segment.id, segment.sequence);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        log::debug!("{}: largest segment ID {:?}", input, summary.max_id);
+        summaries.push(summary);
+    }
+
+    let mut offsets = Vec::with_capacity(gfa.len());
+    let mut next = 0;
+    for summary in &summaries {
+        if shared {
+            offsets.push(0);
+        } else {
+            offsets.push(next);
+            next += summary.max_id.map_or(0, |id| id + 1);
+        }
+    }
+
+    let file = File::create(&output).map_err(|_| CmdError::CreateFileError)?;
+    let mut writer = BufWriter::new(file);
+    gfa::Header::from_tags("1.1".to_string(), tags).write_line(&mut writer)?;
+
+    let mut written_segments: HashSet<usize> = HashSet::new();
+    let mut written_links: HashSet<(usize, bool, usize, bool)> = HashSet::new();
+    for (i, input) in gfa.iter().enumerate() {
+        let offset = offsets[i];
+        let lengths = &summaries[i].lengths;
+        log::info!("Merging {} with ID offset {}", input, offset);
+        for_each_entity(input, &parser, |entity| {
+            match entity {
+                GfaEntity::Header(_) => {}
+                GfaEntity::Segment(mut segment) => {
+                    segment.id += offset;
+                    if !shared || written_segments.insert(segment.id) {
+                        segment.write_line(&mut writer)?;
+                    }
+                }
+                GfaEntity::Link(mut link) => {
+                    link.from_segment += offset;
+                    link.to_segment += offset;
+                    let key = (
+                        link.from_segment,
+                        link.from_orient,
+                        link.to_segment,
+                        link.to_orient,
+                    );
+                    if !shared || written_links.insert(key) {
+                        link.write_line(&mut writer)?;
+                    }
+                }
+                GfaEntity::Walk(mut walk) => {
+                    walk.unit = gfa::walk_unit(offset_steps(walk.extract_step(), offset));
+                    walk.write_line(&mut writer)?;
+                }
+                GfaEntity::Path(mut path) => {
+                    let ranges = path.ranges.unwrap_or_else(|| gfa::Range {
+                        start: 0,
+                        end: path.extract_node().filter_map(|n| lengths.get(&n)).sum(),
+                    });
+                    // overlaps are carried the way convert --to 1.1 does
+                    let has_tags = !path.tags.is_empty();
+                    if let Some(tag) = path
+                        .overlaps
+                        .as_deref()
+                        .and_then(|o| convert::overlap_tag(o, has_tags))
+                    {
+                        path.tags.push(tag);
+                    }
+                    let walk = gfa::Walk {
+                        sample: path.sample.clone(),
+                        haptype: path.haptype.to_string(),
+                        chroms: path.chroms.clone(),
+                        ranges,
+                        unit: gfa::walk_unit(offset_steps(path.extract_step(), offset)),
+                        tags: path.tags,
+                    };
+                    walk.write_line(&mut writer)?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    let mut writer = BufWriter::new(
+        File::create(format!("{}.offsets", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(writer, "input\toffset").map_err(|_| CmdError::WriteError)?;
+    for (input, offset) in gfa.iter().zip(&offsets) {
+        writeln!(writer, "{}\t{}", input, offset).map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn merge(shared: bool, second: &[u8]) -> Result<(String, String), CmdError> {
+        let temp_dir = TempDir::new("test_merge").unwrap();
+        let first_path = temp_dir.path().join("chr1.gfa");
+        let second_path = temp_dir.path().join("chr2.gfa");
+        let output_path = temp_dir.path().join("merged.gfa");
+        File::create(&first_path)
+            .unwrap()
+            .write_all(
+                b"H\tVN:Z:1.1\tRS:Z:ref\n\
+                  S\t1\tACCTT\n\
+                  S\t2\tTCAAGG\n\
+                  L\t1\t+\t2\t-\t0M\n\
+                  W\tref\t0\tchr1\t0\t11\t>1<2\n",
+            )
+            .unwrap();
+        File::create(&second_path)
+            .unwrap()
+            .write_all(second)
+            .unwrap();
+
+        let output = output_path.to_str().unwrap().to_string();
+        run(
+            vec![
+                first_path.to_str().unwrap().to_string(),
+                second_path.to_str().unwrap().to_string(),
+            ],
+            output.clone(),
+            shared,
+        )?;
+        let merged = std::fs::read_to_string(&output).unwrap();
+        let offsets = std::fs::read_to_string(format!("{}.offsets", output)).unwrap();
+        let offsets = offsets
+            .lines()
+            .skip(1)
+            .map(|l| l.rsplit('\t').next().unwrap())
+            .collect::<Vec<_>>()
+            .join(",");
+        Ok((merged, offsets))
+    }
+
+    #[test]
+    fn test_merge_with_offsets() {
+        let (merged, offsets) = merge(
+            false,
+            b"H\tVN:Z:1.0\tRS:Z:ref\n\
+              S\t1\tCTTGATT\n\
+              S\t2\tAA\n\
+              L\t1\t+\t2\t+\t0M\n\
+              P\tref#0#chr2\t1+,2+\t0M\n\
+              P\tref#1#chr2\t2+\t*\tRC:i:1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            "H\tVN:Z:1.1\tRS:Z:ref\n\
+             S\t1\tACCTT\n\
+             S\t2\tTCAAGG\n\
+             L\t1\t+\t2\t-\t0M\n\
+             W\tref\t0\tchr1\t0\t11\t>1<2\n\
+             S\t4\tCTTGATT\n\
+             S\t5\tAA\n\
+             L\t4\t+\t5\t+\t0M\n\
+             W\tref\t0\tchr2\t0\t9\t>4>5\tov:Z:0M\n\
+             W\tref\t1\tchr2\t0\t2\t>5\tRC:i:1\n"
+        );
+        assert_eq!(offsets, "0,3");
+    }
+
+    #[test]
+    fn test_merge_ids_from_zero() {
+        let temp_dir = TempDir::new("test_merge_zero").unwrap();
+        let inputs = [
+            // 7 is only referred to by the L-line
+            b"H\tVN:Z:1.1\tRS:Z:ref\nS\t0\tA\nL\t0\t+\t7\t+\t0M\n".as_slice(),
+            b"H\tVN:Z:1.1\tRS:Z:other\nS\t0\tGG\nW\tother\t0\tchr2\t0\t2\t>0\n",
+        ];
+        let mut paths = Vec::new();
+        for (i, data) in inputs.iter().enumerate() {
+            let path = temp_dir.path().join(format!("{}.gfa", i));
+            File::create(&path).unwrap().write_all(data).unwrap();
+            paths.push(path.to_str().unwrap().to_string());
+        }
+        let output = temp_dir.path().join("merged.gfa");
+        let output = output.to_str().unwrap().to_string();
+        run(paths, output.clone(), false).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "H\tVN:Z:1.1\tRS:Z:ref\n\
+             S\t0\tA\n\
+             L\t0\t+\t7\t+\t0M\n\
+             S\t8\tGG\n\
+             W\tother\t0\tchr2\t0\t2\t>8\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_shared_segments() {
+        let (merged, offsets) = merge(
+            true,
+            b"H\tVN:Z:1.1\n\
+              S\t2\tTCAAGG\n\
+              S\t3\tAA\n\
+              L\t1\t+\t2\t-\t0M\n\
+              L\t2\t-\t3\t+\t0M\n\
+              W\tsample\t0\tchr1\t0\t8\t>2>3\n",
+        )
+        .unwrap();
+        assert_eq!(
+            merged,
+            "H\tVN:Z:1.1\tRS:Z:ref\n\
+             S\t1\tACCTT\n\
+             S\t2\tTCAAGG\n\
+             L\t1\t+\t2\t-\t0M\n\
+             W\tref\t0\tchr1\t0\t11\t>1<2\n\
+             S\t3\tAA\n\
+             L\t2\t-\t3\t+\t0M\n\
+             W\tsample\t0\tchr1\t0\t8\t>2>3\n"
+        );
+        assert_eq!(offsets, "0,0");
+
+        let conflict = merge(true, b"H\tVN:Z:1.1\nS\t2\tGG\n");
+        assert!(matches!(conflict, Err(CmdError::InvalidArgument(_))));
+    }
+}
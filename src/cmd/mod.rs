@@ -1,4 +1,48 @@
+pub mod bed;
+pub mod classify;
 pub mod convert;
+pub mod edges;
 // pub mod gene;
+pub mod growth;
 pub mod index;
+pub mod join;
+mod matrix;
+pub mod merge;
 pub mod pav;
+pub mod renumber;
+pub mod sort;
+pub mod split;
+pub mod strip;
+pub mod viz;
+
+use crate::error::CmdError;
+use std::io::{BufRead, BufReader};
+
+/// Read a node list with one segment ID per line, skipping blank lines.
+pub(crate) fn read_node_list(path: &str) -> Result<Vec<usize>, CmdError> {
+    let reader = BufReader::new(std::fs::File::open(path).map_err(CmdError::FileOpenError)?);
+    let mut nodes = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(CmdError::LineReadError)?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        nodes.push(line.parse::<usize>().map_err(|_| CmdError::ParseError)?);
+    }
+    Ok(nodes)
+}
+
+/// Read a sample list with one name per line, skipping blank lines.
+pub(crate) fn read_sample_list(path: &str) -> Result<Vec<String>, CmdError> {
+    let reader = BufReader::new(std::fs::File::open(path).map_err(CmdError::FileOpenError)?);
+    let mut samples = Vec::new();
+    for line in reader.lines() {
+        let line = line.map_err(CmdError::LineReadError)?;
+        let line = line.trim();
+        if !line.is_empty() {
+            samples.push(line.to_string());
+        }
+    }
+    Ok(samples)
+}
@@ -1,73 +1,731 @@
-use crate::{error::CmdError, gfa};
-use std::io::Write;
-use std::{
-    collections::{HashMap, HashSet},
-    io::{BufRead, BufReader},
-};
+use super::matrix;
+use crate::{binary, error::CmdError, gfa};
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
 
 use log;
-pub fn run(gfa: String, node: String, output: String) -> Result<(), CmdError> {
-    let gfa_parser = gfa::GFAParserBuilder::new().get_walks(true).build();
-    let gfa_obj = gfa_parser.parse_file(gfa)?;
-    log::debug!("GFA file parsed successfully");
-    let reader = BufReader::new(std::fs::File::open(node).map_err(CmdError::FileOpenError)?);
-    let mut avec = HashSet::new();
+
+/// Number of counters held while counting rows, which bounds the memory of
+/// `count_rows` to a chunk of rows however many rows and samples there are.
+const CELLS_PER_CHUNK: usize = 1 << 24;
+
+/// Options of `run` beyond its input and output files.
+#[derive(Debug, Clone)]
+pub struct PavOptions {
+    /// Report base pairs, segment length times copies, instead of copies.
+    pub bp: bool,
+    /// What a column is: `sample`, `haplotype` (`sample#haptype`) or
+    /// `contig` (`sample#haptype#chrom`).
+    pub columns: String,
+    /// How to report the orientation of traversals: `none` (summed),
+    /// `split` (a `+` and a `-` column per sample) or `combined` (`+n/-m`
+    /// cells).
+    pub strand: String,
+    /// `group<TAB>node` file; rows are groups instead of nodes.
+    pub group: Option<String>,
+    /// Fraction of a group's nodes, and of its bp, present to call it
+    /// present.
+    pub threshold: Option<f64>,
+    /// Sample or walk name; rows are windows along its walks instead of nodes.
+    pub reference: Option<String>,
+    /// Window size in bp along the reference.
+    pub window: usize,
+    /// Node matrix format: `tsv`, or `mtx` (Matrix Market), `bits`
+    /// (bit-packed presence) or `npy` with `<output>.rows` and
+    /// `<output>.cols` label files.
+    pub format: String,
+    /// Sample list file; columns follow its order and other samples are
+    /// left out.
+    pub samples: Option<String>,
+}
+
+impl Default for PavOptions {
+    fn default() -> Self {
+        PavOptions {
+            bp: false,
+            columns: "sample".to_string(),
+            strand: "none".to_string(),
+            group: None,
+            threshold: None,
+            reference: None,
+            window: 10000,
+            format: "tsv".to_string(),
+            samples: None,
+        }
+    }
+}
+
+/// Name of the column a walk is counted in.
+pub(crate) fn column_name(walk: &gfa::Walk, columns: &str) -> Result<String, CmdError> {
+    match columns {
+        "sample" => Ok(walk.sample.clone()),
+        "haplotype" => Ok(format!("{}#{}", walk.sample, walk.haptype)),
+        "contig" => Ok(walk.name()),
+        _ => Err(CmdError::InvalidArgument(format!(
+            "unknown columns {}, expected sample, haplotype or contig",
+            columns
+        ))),
+    }
+}
+
+/// Forward and reverse traversals of a node by one column.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Cell {
+    forward: u32,
+    reverse: u32,
+}
+
+impl Cell {
+    pub(crate) fn total(&self) -> u32 {
+        self.forward + self.reverse
+    }
+}
+
+/// The walks of the graph and the matrix column each one is counted in.
+pub(crate) struct Columns<'a> {
+    pub(crate) walks: Vec<&'a gfa::Walk>,
+    pub(crate) of_walk: Vec<usize>,
+    pub(crate) names: Vec<String>,
+}
+
+impl<'a> Columns<'a> {
+    /// Columns in order of first appearance of their walks, or in the order
+    /// of `samples`, whose entries name columns or the samples they belong
+    /// to; walks of columns not in `samples` are left out.
+    pub(crate) fn new(
+        walks: &'a [gfa::Walk],
+        columns: &str,
+        samples: Option<&[String]>,
+    ) -> Result<Self, CmdError> {
+        let keys: Vec<String> = walks
+            .iter()
+            .map(|w| column_name(w, columns))
+            .collect::<Result<_, _>>()?;
+        let rank = |walk: &gfa::Walk, key: &String| match samples {
+            Some(samples) => samples
+                .iter()
+                .position(|s| s == key)
+                .or_else(|| samples.iter().position(|s| *s == walk.sample)),
+            None => Some(0),
+        };
+        let mut selected: Vec<(usize, usize)> = Vec::new();
+        for (i, (walk, key)) in walks.iter().zip(&keys).enumerate() {
+            if let Some(r) = rank(walk, key) {
+                selected.push((r, i));
+            }
+        }
+        if let Some(samples) = samples {
+            for sample in samples {
+                if !walks
+                    .iter()
+                    .zip(&keys)
+                    .any(|(w, k)| k == sample || w.sample == *sample)
+                {
+                    log::warn!("no walk of sample {}", sample);
+                }
+            }
+            if selected.is_empty() {
+                return Err(CmdError::InvalidArgument(
+                    "no walk of the listed samples".to_string(),
+                ));
+            }
+        }
+        // stable, so walks of equal rank keep their order of appearance
+        selected.sort_by_key(|&(r, _)| r);
+
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<&String, usize> = HashMap::new();
+        let mut of_walk = Vec::with_capacity(selected.len());
+        for &(_, i) in &selected {
+            let col = *index.entry(&keys[i]).or_insert_with(|| {
+                names.push(keys[i].clone());
+                names.len() - 1
+            });
+            of_walk.push(col);
+        }
+        Ok(Columns {
+            walks: selected.iter().map(|&(_, i)| &walks[i]).collect(),
+            of_walk,
+            names,
+        })
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Count the traversals of `rows` by every column and hand each row to
+    /// `f` in order, one pass over the walks per chunk of rows, each chunk
+    /// handed over before the next is counted.
+    pub(crate) fn count_rows(
+        &self,
+        rows: &[usize],
+        f: impl FnMut(usize, &[Cell]) -> Result<(), CmdError>,
+    ) -> Result<(), CmdError> {
+        self.count_chunks(rows, (CELLS_PER_CHUNK / self.width().max(1)).max(1), f)
+    }
+
+    fn count_chunks(
+        &self,
+        rows: &[usize],
+        chunk_rows: usize,
+        mut f: impl FnMut(usize, &[Cell]) -> Result<(), CmdError>,
+    ) -> Result<(), CmdError> {
+        let width = self.width();
+        let mut counts = Vec::new();
+        for chunk in rows.chunks(chunk_rows) {
+            let row_of: HashMap<usize, usize> = chunk
+                .iter()
+                .enumerate()
+                .map(|(row, &id)| (id, row))
+                .collect();
+            counts.clear();
+            counts.resize(chunk.len() * width, Cell::default());
+            for (walk, &col) in self.walks.iter().zip(&self.of_walk) {
+                for step in walk.extract_step() {
+                    if let Some(&row) = row_of.get(&step.id) {
+                        let cell = &mut counts[row * width + col];
+                        match step.forward {
+                            true => cell.forward += 1,
+                            false => cell.reverse += 1,
+                        }
+                    }
+                }
+            }
+            for (row, &id) in chunk.iter().enumerate() {
+                f(id, &counts[row * width..(row + 1) * width])?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Features made of several nodes, read from a `group<TAB>node` file.
+struct Groups {
+    names: Vec<String>,
+    /// Every node of any group, in file order.
+    nodes: Vec<usize>,
+    /// The groups each node belongs to.
+    members: HashMap<usize, Vec<usize>>,
+}
+
+fn read_groups(path: &str) -> Result<Groups, CmdError> {
+    let reader = BufReader::new(File::open(path).map_err(CmdError::FileOpenError)?);
+    let mut groups = Groups {
+        names: Vec::new(),
+        nodes: Vec::new(),
+        members: HashMap::new(),
+    };
+    let mut index: HashMap<String, usize> = HashMap::new();
     for line in reader.lines() {
         let line = line.map_err(CmdError::LineReadError)?;
         let line = line.trim();
-        avec.insert(line.parse::<usize>().map_err(|_| CmdError::ParseError)?);
-    }
-    log::debug!("The number of nodes to be analyzed is: {}", avec.len());
-    let mut samples: HashSet<String> = HashSet::new();
-    for walk in &gfa_obj.walks {
-        samples.insert(walk.sample.clone());
-    }
-    let mut matrix: HashMap<&String, HashMap<usize, u32>> = HashMap::with_capacity(samples.len());
-    for sample in &samples {
-        matrix.insert(sample, HashMap::with_capacity(avec.len()));
-    }
-    log::debug!("total number of samples: {}", samples.len());
-    for walk in gfa_obj.walks {
-        for i in walk.extract_node() {
-            for &ii in &avec {
-                if i == ii {
-                    if let Some(m) = matrix.get_mut(&walk.sample) {
-                        if let Some(v) = m.get_mut(&i) {
-                            *v += 1;
-                        } else {
-                            m.insert(i, 1);
-                        }
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let name = fields.next().ok_or(CmdError::ParseError)?;
+        let node = fields
+            .next()
+            .and_then(|n| n.trim().parse::<usize>().ok())
+            .ok_or(CmdError::ParseError)?;
+        let g = *index.entry(name.to_owned()).or_insert_with(|| {
+            groups.names.push(name.to_owned());
+            groups.names.len() - 1
+        });
+        let member_of = groups.members.entry(node).or_insert_with(|| {
+            groups.nodes.push(node);
+            Vec::new()
+        });
+        if !member_of.contains(&g) {
+            member_of.push(g);
+        }
+    }
+    Ok(groups)
+}
+
+/// Presence of the groups in every column, summed over their nodes.
+struct GroupCounts {
+    total: Vec<u64>,
+    present: Vec<u64>,
+}
+
+impl GroupCounts {
+    fn new(groups: usize, width: usize) -> Self {
+        GroupCounts {
+            total: vec![0; groups],
+            present: vec![0; groups * width],
+        }
+    }
+
+    fn add(&mut self, g: usize, row: &[Cell], weight: u64) {
+        let width = row.len();
+        self.total[g] += weight;
+        for (col, cell) in row.iter().enumerate() {
+            if cell.total() > 0 {
+                self.present[g * width + col] += weight;
+            }
+        }
+    }
+
+    /// Write one row per group with the fraction present in each column,
+    /// or 1/0 when a threshold is given.
+    fn write(
+        &self,
+        columns: &Columns,
+        groups: &Groups,
+        threshold: Option<f64>,
+        output: &str,
+    ) -> Result<(), CmdError> {
+        let width = columns.width();
+        let mut writer =
+            BufWriter::new(File::create(output).map_err(|_| CmdError::CreateFileError)?);
+        writeln!(writer, "group\t{}", columns.names.join("\t"))
+            .map_err(|_| CmdError::WriteError)?;
+        for (g, name) in groups.names.iter().enumerate() {
+            let cells: Vec<String> = self.present[g * width..(g + 1) * width]
+                .iter()
+                .map(|&p| {
+                    let fraction = match self.total[g] {
+                        0 => 0.0,
+                        t => p as f64 / t as f64,
+                    };
+                    match threshold {
+                        Some(t) => u8::from(fraction >= t).to_string(),
+                        None => format!("{:.4}", fraction),
                     }
-                }
+                })
+                .collect();
+            writeln!(writer, "{}\t{}", name, cells.join("\t")).map_err(|_| CmdError::WriteError)?;
+        }
+        writer.flush().map_err(|_| CmdError::WriteError)
+    }
+}
+
+/// Write the fraction of each group's nodes present in each column, and to
+/// `<output>.bp.tsv` the fraction of its bp.
+fn write_groups(
+    columns: &Columns,
+    lengths: &HashMap<usize, usize>,
+    groups: &Groups,
+    options: &PavOptions,
+    output: &str,
+) -> Result<(), CmdError> {
+    let width = columns.width();
+    let mut nodes = GroupCounts::new(groups.names.len(), width);
+    let mut bp = GroupCounts::new(groups.names.len(), width);
+    columns.count_rows(&groups.nodes, |id, row| {
+        let len = lengths.get(&id).copied().unwrap_or(0) as u64;
+        for &g in &groups.members[&id] {
+            nodes.add(g, row, 1);
+            bp.add(g, row, len);
+        }
+        Ok(())
+    })?;
+    nodes.write(columns, groups, options.threshold, output)?;
+    bp.write(
+        columns,
+        groups,
+        options.threshold,
+        &format!("{}.bp.tsv", output),
+    )
+}
+
+/// A window of a reference walk, `[start, end)` on its PanSN contig.
+struct Window {
+    contig: String,
+    start: usize,
+    end: usize,
+}
+
+/// Write one BED-like row per window of the reference walks with the
+/// fraction of the window's reference nodes, or bp with `options.bp`, each
+/// other column traverses, and the bp of non-reference nodes each column
+/// inserts after them to `<output>.ins`. Reference walks are taken from
+/// `walks`, so the reference need not be one of the columns.
+fn write_windows(
+    walks: &[gfa::Walk],
+    columns: &Columns,
+    lengths: &HashMap<usize, usize>,
+    reference: &str,
+    options: &PavOptions,
+    output: &str,
+) -> Result<(), CmdError> {
+    if options.window == 0 {
+        return Err(CmdError::InvalidArgument(
+            "the window size must be positive".to_string(),
+        ));
+    }
+    let is_reference = |w: &gfa::Walk| w.sample == reference || w.name() == reference;
+    let len = |id: &usize| lengths.get(id).copied().unwrap_or(0);
+
+    // place every reference node, at its first occurrence, in a window
+    let mut windows: Vec<Window> = Vec::new();
+    let mut ref_index: HashMap<usize, usize> = HashMap::new();
+    let mut ref_window: Vec<usize> = Vec::new();
+    let mut ref_weight: Vec<u64> = Vec::new();
+    for walk in walks.iter().filter(|w| is_reference(w)) {
+        let first = walk.ranges.start / options.window;
+        let mut pos = walk.ranges.start;
+        let mut placed: Vec<(usize, usize)> = Vec::new();
+        for id in walk.extract_node() {
+            placed.push((id, pos / options.window));
+            pos += len(&id);
+        }
+        let end = pos.max(walk.ranges.end);
+        let base = windows.len();
+        for k in first..=(end.max(1) - 1) / options.window {
+            windows.push(Window {
+                contig: walk.name(),
+                start: (k * options.window).max(walk.ranges.start),
+                end: ((k + 1) * options.window).min(end),
+            });
+        }
+        for (id, k) in placed {
+            if let Entry::Vacant(e) = ref_index.entry(id) {
+                e.insert(ref_window.len());
+                ref_window.push(base + k - first);
+                ref_weight.push(match options.bp {
+                    true => len(&id) as u64,
+                    false => 1,
+                });
             }
         }
     }
-    let mut writer = std::fs::File::create(output).map_err(CmdError::FileOpenError)?;
-    let mut header = Vec::new();
-    header.push("node".to_string());
-    for sample in &samples {
-        header.push(sample.to_owned());
-    }
-    writeln!(&mut writer, "{}", header.join("\t")).map_err(|_| CmdError::WriteError)?;
-    for i in avec {
-        let mut tem_vec = Vec::new();
-        for j in &samples {
-            if let Some(m) = matrix.get(j) {
-                if let Some(v) = m.get(&i) {
-                    tem_vec.push(*v);
-                } else {
-                    tem_vec.push(0);
+    if windows.is_empty() {
+        return Err(CmdError::InvalidArgument(format!(
+            "no walk named {}",
+            reference
+        )));
+    }
+
+    // columns made only of reference walks are left out
+    let width = columns.width();
+    let mut other = vec![false; width];
+    for (walk, &col) in columns.walks.iter().zip(&columns.of_walk) {
+        if !is_reference(walk) {
+            other[col] = true;
+        }
+    }
+    let mut total = vec![0u64; windows.len()];
+    for (i, &w) in ref_window.iter().enumerate() {
+        total[w] += ref_weight[i];
+    }
+    let mut present = vec![0u64; windows.len() * width];
+    let mut inserted = vec![0u64; windows.len() * width];
+    let mut seen = vec![false; ref_window.len() * width];
+    for (walk, &col) in columns.walks.iter().zip(&columns.of_walk) {
+        if !other[col] {
+            continue;
+        }
+        let mut last: Option<usize> = None;
+        let mut pending = 0;
+        for id in walk.extract_node() {
+            match ref_index.get(&id) {
+                Some(&r) => {
+                    let w = ref_window[r];
+                    if !seen[r * width + col] {
+                        seen[r * width + col] = true;
+                        present[w * width + col] += ref_weight[r];
+                    }
+                    if last.is_none() {
+                        inserted[w * width + col] += pending;
+                    }
+                    last = Some(w);
                 }
+                None => match last {
+                    Some(w) => inserted[w * width + col] += len(&id) as u64,
+                    None => pending += len(&id) as u64,
+                },
             }
         }
-        let tem: Vec<String> = tem_vec.iter().map(|x| x.to_string()).collect();
-        writeln!(&mut writer, "{}\t{}", i, tem.join("\t")).map_err(|_| CmdError::WriteError)?;
+    }
+
+    let header: Vec<&str> = columns
+        .names
+        .iter()
+        .enumerate()
+        .filter(|(col, _)| other[*col])
+        .map(|(_, name)| name.as_str())
+        .collect();
+    let mut writer = BufWriter::new(File::create(output).map_err(|_| CmdError::CreateFileError)?);
+    let mut ins_writer = BufWriter::new(
+        File::create(format!("{}.ins", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    for w in [&mut writer, &mut ins_writer] {
+        writeln!(w, "#chrom\tstart\tend\t{}", header.join("\t"))
+            .map_err(|_| CmdError::WriteError)?;
+    }
+    for (i, window) in windows.iter().enumerate() {
+        let mut fractions: Vec<String> = Vec::with_capacity(header.len());
+        let mut insertions: Vec<String> = Vec::with_capacity(header.len());
+        for col in (0..width).filter(|&col| other[col]) {
+            let fraction = match total[i] {
+                0 => 0.0,
+                t => present[i * width + col] as f64 / t as f64,
+            };
+            fractions.push(format!("{:.4}", fraction));
+            insertions.push(inserted[i * width + col].to_string());
+        }
+        let bed = format!("{}\t{}\t{}", window.contig, window.start, window.end);
+        writeln!(writer, "{}\t{}", bed, fractions.join("\t")).map_err(|_| CmdError::WriteError)?;
+        writeln!(ins_writer, "{}\t{}", bed, insertions.join("\t"))
+            .map_err(|_| CmdError::WriteError)?;
     }
     writer.flush().map_err(|_| CmdError::WriteError)?;
+    ins_writer.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
+/// Write one row per node, and the per-column bp totals with `options.bp`.
+fn write_nodes(
+    columns: &Columns,
+    lengths: &HashMap<usize, usize>,
+    rows: &[usize],
+    options: &PavOptions,
+    output: &str,
+) -> Result<(), CmdError> {
+    let bp = options.bp;
+    let mut header = Vec::with_capacity(columns.width());
+    for sample in &columns.names {
+        match options.strand.as_str() {
+            "split" => {
+                header.push(format!("{}+", sample));
+                header.push(format!("{}-", sample));
+            }
+            _ => header.push(sample.to_owned()),
+        }
+    }
+    let mut writer = None;
+    let mut matrix = None;
+    match options.format.as_str() {
+        "tsv" => {
+            let mut w =
+                BufWriter::new(File::create(output).map_err(|_| CmdError::CreateFileError)?);
+            writeln!(w, "node\t{}", header.join("\t")).map_err(|_| CmdError::WriteError)?;
+            writer = Some(w);
+        }
+        format => {
+            matrix = Some(matrix::create(format, output, header.len(), bp)?);
+            matrix::write_labels(&format!("{}.rows", output), rows)?;
+            matrix::write_labels(&format!("{}.cols", output), &header)?;
+        }
+    }
+
+    let mut present_bp = vec![0u64; columns.width()];
+    let mut traversed_bp = vec![0u64; columns.width()];
+    columns.count_rows(rows, |id, row| {
+        let len = lengths.get(&id).copied().unwrap_or(0) as u64;
+        let value = |c: u32| match bp {
+            true => c as u64 * len,
+            false => c as u64,
+        };
+        let mut values: Vec<u64> = Vec::with_capacity(row.len() * 2);
+        let mut cells: Vec<String> = Vec::new();
+        for (col, cell) in row.iter().enumerate() {
+            if cell.total() > 0 {
+                present_bp[col] += len;
+            }
+            traversed_bp[col] += cell.total() as u64 * len;
+            match options.strand.as_str() {
+                "split" => {
+                    values.push(value(cell.forward));
+                    values.push(value(cell.reverse));
+                }
+                "combined" => {
+                    cells.push(format!("+{}/-{}", value(cell.forward), value(cell.reverse)))
+                }
+                _ => values.push(value(cell.total())),
+            }
+        }
+        if let Some(matrix) = matrix.as_mut() {
+            return matrix.write_row(&values);
+        }
+        if cells.is_empty() {
+            cells = values.iter().map(|v| v.to_string()).collect();
+        }
+        match writer.as_mut() {
+            Some(w) => {
+                writeln!(w, "{}\t{}", id, cells.join("\t")).map_err(|_| CmdError::WriteError)
+            }
+            None => Ok(()),
+        }
+    })?;
+    if let Some(mut writer) = writer {
+        writer.flush().map_err(|_| CmdError::WriteError)?;
+    }
+    if let Some(matrix) = matrix {
+        matrix.finish()?;
+    }
 
+    if bp {
+        let mut writer = BufWriter::new(
+            File::create(format!("{}.bp.tsv", output)).map_err(|_| CmdError::CreateFileError)?,
+        );
+        writeln!(writer, "sample\tpresent_bp\ttraversed_bp").map_err(|_| CmdError::WriteError)?;
+        for (col, sample) in columns.names.iter().enumerate() {
+            writeln!(
+                writer,
+                "{}\t{}\t{}",
+                sample, present_bp[col], traversed_bp[col]
+            )
+            .map_err(|_| CmdError::WriteError)?;
+        }
+        writer.flush().map_err(|_| CmdError::WriteError)?;
+    }
     Ok(())
 }
 
+/// Parse the walks of `gfa` and the length of every segment, those of an
+/// index from the GFA it was built from.
+///
+/// The segments of a binary or JSON Lines graph come from the same parse
+/// as its walks and their sequences are dropped once measured; those of a
+/// GFA file are measured in a pass that does not hold the sequences.
+pub(crate) fn load_graph(gfa: &str) -> Result<(gfa::GFA, HashMap<usize, usize>), CmdError> {
+    let source = gfa::graph_source(gfa);
+    let structured = binary::is_binary(source)? || source.ends_with(".jsonl");
+    let mut gfa_obj = gfa::GFAParserBuilder::new()
+        .get_segments(structured && source == gfa)
+        .get_walks(true)
+        .build()
+        .parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+    let lengths = match (structured, source == gfa) {
+        (true, true) => gfa_obj.get_segment_len(),
+        (true, false) => gfa::GFAParserBuilder::new()
+            .get_segments(true)
+            .build()
+            .parse_file(source)?
+            .get_segment_len(),
+        (false, _) => super::convert::segment_lengths(source)?,
+    };
+    gfa_obj.segments = Vec::new();
+    Ok((gfa_obj, lengths))
+}
+
+/// Every segment of the graph, or the IDs seen in walks when it has no
+/// S-lines, sorted by ID.
+pub(crate) fn graph_nodes(gfa_obj: &gfa::GFA, lengths: &HashMap<usize, usize>) -> Vec<usize> {
+    let mut ids: Vec<usize> = match lengths.is_empty() {
+        false => lengths.keys().copied().collect(),
+        true => gfa_obj
+            .walks
+            .iter()
+            .flat_map(|w| w.extract_node())
+            .collect::<HashSet<usize>>()
+            .into_iter()
+            .collect(),
+    };
+    ids.sort_unstable();
+    ids
+}
+
+/// Write the presence/absence matrix of nodes over samples, the number of
+/// times each sample's walks step on each node.
+///
+/// Rows are the nodes of the `node` list in its order, or every segment of
+/// the graph (the IDs seen in walks when it has no S-lines) sorted by ID
+/// without one.
+///
+/// Columns are samples, or haplotypes or haplotype contigs depending on
+/// `options.columns`, in the order their first walk appears or in the order
+/// of the `options.samples` list, which also selects them. With `bp`, cells
+/// hold the base pairs covered, segment length times copies, and the bp
+/// present in and traversed by each sample are written to `<output>.bp.tsv`.
+/// `options.strand` reports forward and reverse traversals separately. With
+/// `options.group`, rows are groups of nodes instead, with the fraction of
+/// their nodes and of their bp present written to `<output>` and
+/// `<output>.bp.tsv`, and with `options.reference` windows along a
+/// reference, which need not be one of the listed samples.
+/// Node matrices can also be written sparse, bit-packed or as `.npy` with
+/// `options.format`, their labels going to `<output>.rows` and
+/// `<output>.cols`.
+pub fn run(
+    gfa: String,
+    node: Option<String>,
+    output: String,
+    options: PavOptions,
+) -> Result<(), CmdError> {
+    if !["none", "split", "combined"].contains(&options.strand.as_str()) {
+        return Err(CmdError::InvalidArgument(format!(
+            "unknown strand {}, expected none, split or combined",
+            options.strand
+        )));
+    }
+    if !["tsv", "mtx", "bits", "npy"].contains(&options.format.as_str()) {
+        return Err(CmdError::InvalidArgument(format!(
+            "unknown format {}, expected tsv, mtx, bits or npy",
+            options.format
+        )));
+    }
+    if options.format != "tsv"
+        && (options.group.is_some() || options.reference.is_some() || options.strand == "combined")
+    {
+        return Err(CmdError::InvalidArgument(format!(
+            "format {} only writes node matrices with numeric cells",
+            options.format
+        )));
+    }
+    if (options.group.is_some() || options.reference.is_some()) && options.strand != "none" {
+        return Err(CmdError::InvalidArgument(format!(
+            "strand {} only applies to node matrices",
+            options.strand
+        )));
+    }
+    if options.group.is_some() && options.bp {
+        return Err(CmdError::InvalidArgument(
+            "groups always get bp fractions, in <output>.bp.tsv, without --bp".to_string(),
+        ));
+    }
+    let samples = match &options.samples {
+        Some(path) => Some(super::read_sample_list(path)?),
+        None => None,
+    };
+    let (gfa_obj, lengths) = load_graph(&gfa)?;
+    let columns = Columns::new(&gfa_obj.walks, &options.columns, samples.as_deref())?;
+    log::debug!("total number of samples: {}", columns.width());
+
+    if let Some(group) = &options.group {
+        let groups = read_groups(group)?;
+        log::debug!(
+            "{} groups of {} nodes to be analyzed",
+            groups.names.len(),
+            groups.nodes.len()
+        );
+        return write_groups(&columns, &lengths, &groups, &options, &output);
+    }
+    if let Some(reference) = &options.reference {
+        return write_windows(
+            &gfa_obj.walks,
+            &columns,
+            &lengths,
+            reference,
+            &options,
+            &output,
+        );
+    }
+
+    let rows: Vec<usize> = match node {
+        Some(node) => {
+            let mut seen = HashSet::new();
+            super::read_node_list(&node)?
+                .into_iter()
+                .filter(|n| seen.insert(*n))
+                .collect()
+        }
+        None => graph_nodes(&gfa_obj, &lengths),
+    };
+    log::debug!("The number of nodes to be analyzed is: {}", rows.len());
+    let unknown = rows.iter().filter(|n| !lengths.contains_key(n)).count();
+    if options.bp && unknown > 0 {
+        log::warn!("{} nodes have no S-line and count as 0 bp", unknown);
+    }
+
+    write_nodes(&columns, &lengths, &rows, &options, &output)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,46 +766,473 @@ mod tests {
         let node_file_str = node_file_path.to_str().unwrap().to_string();
         let output_file_str = output_file_path.to_str().unwrap().to_string();
 
-        let result = run(gfa_file_str, node_file_str, output_file_str.clone());
+        let result = run(
+            gfa_file_str,
+            Some(node_file_str),
+            output_file_str.clone(),
+            PavOptions::default(),
+        );
 
         assert!(result.is_ok());
-        let output_content = std::fs::read_to_string(output_file_path).unwrap();
+        let output_content = std::fs::read_to_string(&output_file_path).unwrap();
         let expected_content = "node\tsample1\tsample2\tsample3\tsample4\
             \tsample5\n11\t1\t1\t1\t2\t1\n12\t1\t1\t0\
             \t0\t1\n13\t1\t0\t1\t0\t0\n";
+        assert_eq!(output_content, expected_content);
 
-        let output_lines: Vec<String> = output_content
-            .trim()
-            .split('\n')
-            .map(String::from)
-            .collect();
-        let expected_lines: Vec<String> = expected_content
-            .trim()
-            .split('\n')
-            .map(String::from)
-            .collect();
+        // columns follow a sample list, which leaves the others out
+        let sample_file_path = temp_dir.path().join("samples.txt");
+        std::fs::write(&sample_file_path, "sample4\nsample1\n\nsample9\n").unwrap();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output_file_str,
+            PavOptions {
+                samples: Some(sample_file_path.to_str().unwrap().to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output_file_path).unwrap(),
+            "node\tsample4\tsample1\n11\t2\t1\n12\t0\t1\n13\t0\t1\n"
+        );
+    }
 
-        let mut output_columns: Vec<String> = output_lines
-            .iter()
-            .map(|line| {
-                let mut columns: Vec<&str> = line.split('\t').collect();
-                columns.sort();
-                columns.join("\t")
-            })
-            .collect();
+    #[test]
+    fn test_count_chunks() {
+        let temp_dir = TempDir::new("test_count_chunks").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let big = 1usize << 60;
+        std::fs::write(
+            &gfa_file_path,
+            format!(
+                "W\tsample1\t0\tchr1\t0\t3\t>11<{}>13\n\
+                 W\tsample2\t0\tchr1\t0\t2\t<11<11\n",
+                big
+            ),
+        )
+        .unwrap();
+        let (gfa_obj, _) = load_graph(gfa_file_path.to_str().unwrap()).unwrap();
+        let columns = Columns::new(&gfa_obj.walks, "sample", None).unwrap();
+        let rows = [13, big, 11, 12];
+        let count = |chunk_rows: usize| {
+            let mut seen = Vec::new();
+            columns
+                .count_chunks(&rows, chunk_rows, |id, row| {
+                    let cells = row.iter().map(|c| (c.forward, c.reverse));
+                    seen.push((id, cells.collect::<Vec<_>>()));
+                    Ok(())
+                })
+                .unwrap();
+            seen
+        };
+        let expected = vec![
+            (13, vec![(1, 0), (0, 0)]),
+            (big, vec![(0, 1), (0, 0)]),
+            (11, vec![(1, 0), (0, 2)]),
+            (12, vec![(0, 0), (0, 0)]),
+        ];
+        assert_eq!(count(1), expected);
+        assert_eq!(count(3), expected);
+        assert_eq!(count(CELLS_PER_CHUNK), expected);
+    }
 
-        let mut expected_columns: Vec<String> = expected_lines
-            .iter()
-            .map(|line| {
-                let mut columns: Vec<&str> = line.split('\t').collect();
-                columns.sort();
-                columns.join("\t")
-            })
+    #[test]
+    fn test_pav_all_nodes() {
+        let temp_dir = TempDir::new("test_pav_all_nodes").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tA\n\
+            W\tsample1\t0\tchr1\t0\t18\t>11<12>13\n\
+            W\tsample1\t1\tchr1\t0\t10\t>11>11\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output.clone(),
+            PavOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "node\tsample1\n11\t3\n12\t1\n13\t1\n14\t0\n"
+        );
+
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output.clone(),
+            PavOptions {
+                bp: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "node\tsample1\n11\t15\n12\t6\n13\t7\n14\t0\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.bp.tsv", output)).unwrap(),
+            "sample\tpresent_bp\ttraversed_bp\nsample1\t18\t28\n"
+        );
+
+        // an index has the steps and lengths of the GFA it was built from
+        let gfa_file = gfa_file_path.to_str().unwrap().to_string();
+        crate::cmd::index::build(&gfa_file).unwrap();
+        run(
+            format!("{}.simple", gfa_file),
+            None,
+            output.clone(),
+            PavOptions {
+                bp: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "node\tsample1\n11\t15\n12\t6\n13\t7\n14\t0\n"
+        );
+
+        // so has a binary graph, from the one parse of its walks
+        let pgb = format!("{}.pgb", gfa_file);
+        crate::cmd::convert::convert_binary(gfa_file, pgb.clone()).unwrap();
+        run(
+            pgb,
+            None,
+            output.clone(),
+            PavOptions {
+                bp: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "node\tsample1\n11\t15\n12\t6\n13\t7\n14\t0\n"
+        );
+    }
+
+    /// Matrix columns by header name, independent of the column order.
+    fn read_columns(path: &str) -> HashMap<String, Vec<String>> {
+        let content = std::fs::read_to_string(path).unwrap();
+        let mut lines = content.lines();
+        let header: Vec<&str> = lines.next().unwrap().split('\t').collect();
+        let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+        for line in lines {
+            for (name, cell) in header.iter().zip(line.split('\t')) {
+                columns
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(cell.to_string());
+            }
+        }
+        columns
+    }
+
+    #[test]
+    fn test_pav_haplotype_columns() {
+        let temp_dir = TempDir::new("test_pav_haplotype_columns").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            W\tsample1\t1\tchr1\t0\t11\t>11<12\n\
+            W\tsample1\t2\tchr1\t0\t5\t>11\n\
+            W\tsample1\t2\tchr2\t0\t6\t>12\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        let pav = |columns: &str| {
+            run(
+                gfa_file_path.to_str().unwrap().to_string(),
+                None,
+                output.clone(),
+                PavOptions {
+                    columns: columns.to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            read_columns(&output)
+        };
+
+        let columns = pav("haplotype");
+        assert_eq!(columns.len(), 3);
+        assert_eq!(columns["sample1#1"], ["1", "1"]);
+        assert_eq!(columns["sample1#2"], ["1", "1"]);
+
+        let columns = pav("contig");
+        assert_eq!(columns.len(), 4);
+        assert_eq!(columns["sample1#1#chr1"], ["1", "1"]);
+        assert_eq!(columns["sample1#2#chr1"], ["1", "0"]);
+        assert_eq!(columns["sample1#2#chr2"], ["0", "1"]);
+    }
+
+    #[test]
+    fn test_pav_strand() {
+        let temp_dir = TempDir::new("test_pav_strand").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+            W\tsample2\t0\tchr1\t0\t16\t>11>12<11\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        let pav = |strand: &str| {
+            run(
+                gfa_file_path.to_str().unwrap().to_string(),
+                None,
+                output.clone(),
+                PavOptions {
+                    strand: strand.to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            read_columns(&output)
+        };
+
+        let columns = pav("split");
+        assert_eq!(columns.len(), 5);
+        assert_eq!(columns["sample1+"], ["1", "0"]);
+        assert_eq!(columns["sample1-"], ["0", "1"]);
+        assert_eq!(columns["sample2+"], ["1", "1"]);
+        assert_eq!(columns["sample2-"], ["1", "0"]);
+
+        let columns = pav("combined");
+        assert_eq!(columns["sample1"], ["+1/-0", "+0/-1"]);
+        assert_eq!(columns["sample2"], ["+1/-1", "+1/-0"]);
+    }
+
+    #[test]
+    fn test_pav_formats() {
+        let temp_dir = TempDir::new("test_pav_formats").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            W\tsample1\t0\tchr1\t0\t16\t>11<12>11\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        let pav = |format: &str| {
+            run(
+                gfa_file_path.to_str().unwrap().to_string(),
+                None,
+                output.clone(),
+                PavOptions {
+                    strand: "split".to_string(),
+                    format: format.to_string(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            std::fs::read(&output).unwrap()
+        };
+
+        let mtx = String::from_utf8(pav("mtx")).unwrap();
+        let mut lines = mtx.lines();
+        assert_eq!(
+            lines.next(),
+            Some("%%MatrixMarket matrix coordinate integer general")
+        );
+        let size: Vec<&str> = lines.next().unwrap().split_whitespace().collect();
+        assert_eq!(size, ["3", "2", "2"]);
+        assert_eq!(lines.collect::<Vec<_>>(), ["1 1 2", "2 2 1"]);
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.rows", output)).unwrap(),
+            "11\n12\n13\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.cols", output)).unwrap(),
+            "sample1+\nsample1-\n"
+        );
+
+        let bits = pav("bits");
+        assert_eq!(&bits[..4], b"PAVB");
+        assert_eq!(bits[4..12], 3u64.to_le_bytes());
+        assert_eq!(bits[12..20], 2u64.to_le_bytes());
+        assert_eq!(bits[20..], [1, 2, 0]);
+
+        let npy = pav("npy");
+        assert_eq!(npy.len(), 128 + 6 * 4);
+        let header = String::from_utf8_lossy(&npy[10..128]);
+        assert!(header.contains("'descr': '<u4'"));
+        assert!(header.contains("'shape': (3, 2)"));
+        let values: Vec<u32> = npy[128..]
+            .chunks(4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
             .collect();
+        assert_eq!(values, [2, 0, 0, 1, 0, 0]);
+
+        let combined = run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output.clone(),
+            PavOptions {
+                strand: "combined".to_string(),
+                format: "npy".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(matches!(combined, Err(CmdError::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn test_pav_groups() {
+        let temp_dir = TempDir::new("test_pav_groups").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let group_file_path = temp_dir.path().join("groups.tsv");
+        let output_file_path = temp_dir.path().join("output.tsv");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            W\tsample1\t0\tchr1\t0\t18\t>11<12>13\n\
+            W\tsample2\t0\tchr1\t0\t5\t>11\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        File::create(&group_file_path)
+            .unwrap()
+            .write_all(b"geneA\t11\ngeneA\t12\ngeneB\t13\n")
+            .unwrap();
+
+        let output = output_file_path.to_str().unwrap().to_string();
+        let bp_output = format!("{}.bp.tsv", output);
+        let group = Some(group_file_path.to_str().unwrap().to_string());
+        let pav = |options: PavOptions| {
+            run(
+                gfa_file_path.to_str().unwrap().to_string(),
+                None,
+                output.clone(),
+                PavOptions {
+                    group: group.clone(),
+                    ..options
+                },
+            )
+        };
+
+        pav(PavOptions::default()).unwrap();
+        let columns = read_columns(&output);
+        assert_eq!(columns["group"], ["geneA", "geneB"]);
+        assert_eq!(columns["sample1"], ["1.0000", "1.0000"]);
+        assert_eq!(columns["sample2"], ["0.5000", "0.0000"]);
+        let columns = read_columns(&bp_output);
+        assert_eq!(columns["sample2"], ["0.4545", "0.0000"]);
+
+        pav(PavOptions {
+            threshold: Some(0.5),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(read_columns(&output)["sample2"], ["1", "0"]);
+        assert_eq!(read_columns(&bp_output)["sample2"], ["0", "0"]);
+
+        for options in [
+            PavOptions {
+                bp: true,
+                ..Default::default()
+            },
+            PavOptions {
+                strand: "split".to_string(),
+                ..Default::default()
+            },
+            PavOptions {
+                format: "npy".to_string(),
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(pav(options), Err(CmdError::InvalidArgument(_))));
+        }
+    }
+
+    #[test]
+    fn test_pav_windows() {
+        let temp_dir = TempDir::new("test_pav_windows").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output.bed");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tAA\n\
+            W\tref\t0\tchr1\t0\t18\t>11>12>13\n\
+            W\tsample2\t0\tchr1\t0\t14\t>11>14>13\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
 
-        output_columns.sort();
-        expected_columns.sort();
+        let output = output_file_path.to_str().unwrap().to_string();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output.clone(),
+            PavOptions {
+                reference: Some("ref".to_string()),
+                window: 10,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&output).unwrap(),
+            "#chrom\tstart\tend\tsample2\n\
+             ref#0#chr1\t0\t10\t0.5000\n\
+             ref#0#chr1\t10\t18\t1.0000\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.ins", output)).unwrap(),
+            "#chrom\tstart\tend\tsample2\n\
+             ref#0#chr1\t0\t10\t2\n\
+             ref#0#chr1\t10\t18\t0\n"
+        );
 
-        assert_eq!(output_columns, expected_columns);
+        // the reference is found even when the sample list leaves it out
+        let sample_file_path = temp_dir.path().join("samples.txt");
+        std::fs::write(&sample_file_path, "sample2\n").unwrap();
+        let expected = std::fs::read_to_string(&output).unwrap();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            None,
+            output.clone(),
+            PavOptions {
+                reference: Some("ref".to_string()),
+                window: 10,
+                samples: Some(sample_file_path.to_str().unwrap().to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
     }
 }
@@ -0,0 +1,276 @@
+use crate::{error::CmdError, gfa};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+/// Every segment ID used by the graph, including IDs only referenced by
+/// links or walks.
+fn all_ids(gfa_obj: &gfa::GFA) -> BTreeSet<usize> {
+    let mut ids: BTreeSet<usize> = gfa_obj.segments.iter().map(|s| s.id).collect();
+    for link in &gfa_obj.links {
+        ids.insert(link.from_segment);
+        ids.insert(link.to_segment);
+    }
+    for walk in &gfa_obj.walks {
+        ids.extend(walk.extract_node());
+    }
+    for path in &gfa_obj.paths {
+        ids.extend(path.extract_node());
+    }
+    ids
+}
+
+/// Kahn's algorithm over the links as written, always taking the smallest
+/// ready ID; cycles are broken by taking the smallest unvisited ID.
+fn topological_order(gfa_obj: &gfa::GFA, ids: &BTreeSet<usize>) -> Vec<usize> {
+    let mut successors: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut in_degree: HashMap<usize, usize> = ids.iter().map(|&id| (id, 0)).collect();
+    for link in &gfa_obj.links {
+        if link.from_segment == link.to_segment {
+            continue;
+        }
+        successors
+            .entry(link.from_segment)
+            .or_default()
+            .push(link.to_segment);
+        *in_degree.entry(link.to_segment).or_default() += 1;
+    }
+    let mut ready: BinaryHeap<Reverse<usize>> = in_degree
+        .iter()
+        .filter(|(_, &d)| d == 0)
+        .map(|(&id, _)| Reverse(id))
+        .collect();
+    let mut visited: HashSet<usize> = HashSet::with_capacity(ids.len());
+    let mut order = Vec::with_capacity(ids.len());
+    let mut remaining = ids.iter();
+    while order.len() < ids.len() {
+        let id = match ready.pop() {
+            Some(Reverse(id)) => id,
+            None => match remaining.find(|id| !visited.contains(id)) {
+                Some(&id) => id,
+                None => break,
+            },
+        };
+        if !visited.insert(id) {
+            continue;
+        }
+        order.push(id);
+        if let Some(next) = successors.get(&id) {
+            for &n in next {
+                if let Some(d) = in_degree.get_mut(&n) {
+                    *d = d.saturating_sub(1);
+                    if *d == 0 && !visited.contains(&n) {
+                        ready.push(Reverse(n));
+                    }
+                }
+            }
+        }
+    }
+    order
+}
+
+/// Nodes in order of first appearance along the reference walks or paths,
+/// followed by the remaining nodes sorted by ID.
+fn reference_order(
+    gfa_obj: &gfa::GFA,
+    ids: &BTreeSet<usize>,
+    reference: &str,
+) -> Result<Vec<usize>, CmdError> {
+    let mut seen: HashSet<usize> = HashSet::with_capacity(ids.len());
+    let mut order = Vec::with_capacity(ids.len());
+    let mut found = false;
+    let walks = gfa_obj
+        .walks
+        .iter()
+        .filter(|w| w.sample == reference || w.name() == reference)
+        .map(|w| w.extract_node());
+    let paths = gfa_obj
+        .paths
+        .iter()
+        .filter(|p| {
+            p.sample == reference || format!("{}#{}#{}", p.sample, p.haptype, p.chroms) == reference
+        })
+        .map(|p| p.extract_node());
+    for nodes in walks.chain(paths) {
+        found = true;
+        for node in nodes {
+            if seen.insert(node) {
+                order.push(node);
+            }
+        }
+    }
+    if !found {
+        return Err(CmdError::InvalidArgument(format!(
+            "no walk or path named {}",
+            reference
+        )));
+    }
+    order.extend(ids.iter().filter(|id| !seen.contains(id)));
+    Ok(order)
+}
+
+fn remap_steps(steps: gfa::StepIterator, mapping: &HashMap<usize, usize>) -> Vec<gfa::Step> {
+    steps
+        .map(|s| gfa::Step {
+            id: mapping[&s.id],
+            forward: s.forward,
+        })
+        .collect()
+}
+
+/// Assign contiguous segment IDs starting from 1, rewrite every record and
+/// write the old to new translation table next to the output.
+///
+/// `order` is `id` (keep the current ID order), `topo` (topological order
+/// of the links) or `path:<name>` (order along a reference sample or walk).
+pub fn run(
+    gfa: String,
+    output: String,
+    order: String,
+    node: Option<String>,
+) -> Result<(), CmdError> {
+    let gfa_parser = gfa::GFAParserBuilder::all().build();
+    let mut gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let ids = all_ids(&gfa_obj);
+    let new_order: Vec<usize> = match order.as_str() {
+        "id" => ids.iter().copied().collect(),
+        "topo" => topological_order(&gfa_obj, &ids),
+        _ => match order.strip_prefix("path:") {
+            Some(reference) => reference_order(&gfa_obj, &ids, reference)?,
+            None => {
+                return Err(CmdError::InvalidArgument(format!(
+                    "unknown order {}, expected id, topo or path:<name>",
+                    order
+                )))
+            }
+        },
+    };
+    let mapping: HashMap<usize, usize> = new_order
+        .iter()
+        .enumerate()
+        .map(|(i, &old)| (old, i + 1))
+        .collect();
+    log::debug!("{} segment IDs renumbered", mapping.len());
+
+    for segment in gfa_obj.segments.iter_mut() {
+        segment.id = mapping[&segment.id];
+    }
+    gfa_obj.segments.sort_by_key(|s| s.id);
+    for link in gfa_obj.links.iter_mut() {
+        link.from_segment = mapping[&link.from_segment];
+        link.to_segment = mapping[&link.to_segment];
+    }
+    for walk in gfa_obj.walks.iter_mut() {
+        walk.unit = gfa::walk_unit(remap_steps(walk.extract_step(), &mapping));
+    }
+    for path in gfa_obj.paths.iter_mut() {
+        path.unit = gfa::path_unit(remap_steps(path.extract_step(), &mapping));
+    }
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    gfa_obj.write_to(&mut writer)?;
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    let mut table = BufWriter::new(
+        File::create(format!("{}.map", output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(table, "old\tnew").map_err(|_| CmdError::WriteError)?;
+    for (i, old) in new_order.iter().enumerate() {
+        writeln!(table, "{}\t{}", old, i + 1).map_err(|_| CmdError::WriteError)?;
+    }
+    table.flush().map_err(|_| CmdError::WriteError)?;
+
+    if let Some(node) = node {
+        let nodes = super::read_node_list(&node)?;
+        let mut writer = BufWriter::new(
+            File::create(format!("{}.renumbered", node)).map_err(|_| CmdError::CreateFileError)?,
+        );
+        for n in nodes {
+            match mapping.get(&n) {
+                Some(new) => writeln!(writer, "{}", new).map_err(|_| CmdError::WriteError)?,
+                None => log::warn!("Node {} is not in the graph", n),
+            }
+        }
+        writer.flush().map_err(|_| CmdError::WriteError)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    const GFA_DATA: &[u8] = b"H\tVN:Z:1.1\n\
+        S\t300\tACCTT\n\
+        S\t20\tTCAAGG\n\
+        S\t1000\tCTTGATT\n\
+        L\t1000\t+\t20\t-\t0M\n\
+        L\t20\t-\t300\t+\t0M\n\
+        W\tref\t0\tchr1\t0\t18\t>1000<20>300\n\
+        W\tsample\t0\tchr1\t0\t12\t>300<1000\n";
+
+    fn renumber(order: &str) -> (String, String, String) {
+        let temp_dir = TempDir::new("test_renumber").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let node_file_path = temp_dir.path().join("test.nodes");
+        let output_file_path = temp_dir.path().join("output.gfa");
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(GFA_DATA)
+            .unwrap();
+        let mut node_file = File::create(&node_file_path).unwrap();
+        writeln!(node_file, "300").unwrap();
+        writeln!(node_file, "1000").unwrap();
+
+        let output_file_str = output_file_path.to_str().unwrap().to_string();
+        let node_file_str = node_file_path.to_str().unwrap().to_string();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            output_file_str.clone(),
+            order.to_string(),
+            Some(node_file_str.clone()),
+        )
+        .unwrap();
+        (
+            std::fs::read_to_string(&output_file_str).unwrap(),
+            std::fs::read_to_string(format!("{}.map", output_file_str)).unwrap(),
+            std::fs::read_to_string(format!("{}.renumbered", node_file_str)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_renumber_by_id() {
+        let (gfa, table, nodes) = renumber("id");
+        assert_eq!(
+            gfa,
+            "H\tVN:Z:1.1\n\
+             S\t1\tTCAAGG\n\
+             S\t2\tACCTT\n\
+             S\t3\tCTTGATT\n\
+             L\t3\t+\t1\t-\t0M\n\
+             L\t1\t-\t2\t+\t0M\n\
+             W\tref\t0\tchr1\t0\t18\t>3<1>2\n\
+             W\tsample\t0\tchr1\t0\t12\t>2<3\n"
+        );
+        assert_eq!(table, "old\tnew\n20\t1\n300\t2\n1000\t3\n");
+        assert_eq!(nodes, "2\n3\n");
+    }
+
+    #[test]
+    fn test_renumber_by_topology_and_path() {
+        let (gfa, _, _) = renumber("topo");
+        assert!(gfa.contains("W\tref\t0\tchr1\t0\t18\t>1<2>3\n"));
+        let (gfa, table, _) = renumber("path:ref#0#chr1");
+        assert!(gfa.contains("W\tref\t0\tchr1\t0\t18\t>1<2>3\n"));
+        assert_eq!(table, "old\tnew\n1000\t1\n20\t2\n300\t3\n");
+    }
+}
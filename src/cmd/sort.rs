@@ -0,0 +1,328 @@
+use crate::gfa::{GfaEntity, GfaWritable};
+use crate::{binary, error::CmdError, gfa};
+use bstr::io::BufReadExt;
+use bstr::ByteSlice;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tempdir::TempDir;
+
+use log;
+
+/// Largest number of run files merged at once; more runs are merged in
+/// passes so the number of open files stays bounded.
+const MAX_FAN_IN: usize = 64;
+
+/// Haplotypes sort numerically when they are numbers, so `2` comes before
+/// `10`, and after all numbers otherwise.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Haplotype {
+    Number(u64),
+    Name(String),
+}
+
+impl From<String> for Haplotype {
+    fn from(haptype: String) -> Self {
+        match haptype.parse() {
+            Ok(n) => Haplotype::Number(n),
+            Err(_) => Haplotype::Name(haptype),
+        }
+    }
+}
+
+/// Position of a record in the sorted output. The variant order puts
+/// segments before links, links before paths and paths before walks.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Segment(usize),
+    Link(usize, char, usize, char),
+    Path(String, Haplotype, String, usize, usize),
+    Walk(String, Haplotype, String, usize, usize),
+}
+
+/// A record and the line it was read from; ties are broken on the line so
+/// the order is total.
+type Entry = (SortKey, Vec<u8>);
+
+/// Sort key of a record, `None` for headers which keep their input order.
+fn sort_key(entity: GfaEntity) -> Option<SortKey> {
+    match entity {
+        GfaEntity::Header(_) => None,
+        GfaEntity::Segment(s) => Some(SortKey::Segment(s.id)),
+        GfaEntity::Link(l) => Some(SortKey::Link(
+            l.from_segment,
+            gfa::orient_char(l.from_orient),
+            l.to_segment,
+            gfa::orient_char(l.to_orient),
+        )),
+        GfaEntity::Path(p) => {
+            let (start, end) = p.ranges.map_or((0, 0), |r| (r.start, r.end));
+            Some(SortKey::Path(
+                p.sample,
+                Haplotype::Number(p.haptype.into()),
+                p.chroms,
+                start,
+                end,
+            ))
+        }
+        GfaEntity::Walk(w) => Some(SortKey::Walk(
+            w.sample,
+            w.haptype.into(),
+            w.chroms,
+            w.ranges.start,
+            w.ranges.end,
+        )),
+    }
+}
+
+/// Sort `entries` and write them to a new run file in `dir`.
+fn spill(entries: &mut Vec<Entry>, dir: &Path, n: usize) -> Result<PathBuf, CmdError> {
+    entries.sort();
+    let path = dir.join(format!("run{}.gfa", n));
+    let mut writer = BufWriter::new(File::create(&path).map_err(|_| CmdError::CreateFileError)?);
+    for (_, line) in entries.drain(..) {
+        writer.write_all(&line).map_err(|_| CmdError::WriteError)?;
+        writer.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+    log::debug!("Wrote sorted run {}", path.display());
+    Ok(path)
+}
+
+fn next_entry(
+    lines: &mut impl Iterator<Item = std::io::Result<Vec<u8>>>,
+    parser: &gfa::GFAParser,
+) -> Result<Option<Entry>, CmdError> {
+    match lines.next() {
+        None => Ok(None),
+        Some(line) => {
+            let line = line.map_err(CmdError::LineReadError)?;
+            let key = parser
+                .parse_gfa_line(&line)?
+                .and_then(sort_key)
+                .ok_or(CmdError::ParseError)?;
+            Ok(Some((key, line)))
+        }
+    }
+}
+
+/// K-way merge of sorted run files.
+fn merge_runs<W: Write>(
+    runs: &[PathBuf],
+    parser: &gfa::GFAParser,
+    writer: &mut W,
+) -> Result<(), CmdError> {
+    let mut readers = Vec::with_capacity(runs.len());
+    for path in runs {
+        let file = File::open(path).map_err(CmdError::FileOpenError)?;
+        readers.push(BufReader::new(file).byte_lines());
+    }
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        if let Some(entry) = next_entry(reader, parser)? {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+    while let Some(Reverse(((_, line), i))) = heap.pop() {
+        writer.write_all(&line).map_err(|_| CmdError::WriteError)?;
+        writer.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
+        if let Some(entry) = next_entry(&mut readers[i], parser)? {
+            heap.push(Reverse((entry, i)));
+        }
+    }
+    Ok(())
+}
+
+/// Merge runs `fan_in` at a time into new runs in `dir` until at most
+/// `fan_in` are left.
+fn merge_passes(
+    mut runs: Vec<PathBuf>,
+    dir: &Path,
+    fan_in: usize,
+    parser: &gfa::GFAParser,
+) -> Result<Vec<PathBuf>, CmdError> {
+    let mut n = runs.len();
+    while runs.len() > fan_in {
+        log::debug!("Merging {} sorted runs {} at a time", runs.len(), fan_in);
+        let mut merged = Vec::with_capacity(runs.len().div_ceil(fan_in));
+        for chunk in runs.chunks(fan_in) {
+            let path = dir.join(format!("run{}.gfa", n));
+            n += 1;
+            let mut writer =
+                BufWriter::new(File::create(&path).map_err(|_| CmdError::CreateFileError)?);
+            merge_runs(chunk, parser, &mut writer)?;
+            writer.flush().map_err(|_| CmdError::WriteError)?;
+            for run in chunk {
+                std::fs::remove_file(run).map_err(|_| CmdError::WriteError)?;
+            }
+            merged.push(path);
+        }
+        runs = merged;
+    }
+    Ok(runs)
+}
+
+/// Write the records of a GFA file in canonical order: headers in input
+/// order, segments by ID, links by (from, orient, to, orient), then paths
+/// and walks by PanSN name and start. Lines are copied unchanged.
+///
+/// With `buffer`, at most that many records are held in memory; sorted runs
+/// are written to a temporary directory next to the output and merged, at
+/// most `MAX_FAN_IN` at a time.
+pub fn run(gfa: String, output: String, buffer: Option<usize>) -> Result<(), CmdError> {
+    sort(gfa, output, buffer, MAX_FAN_IN)
+}
+
+fn sort(gfa: String, output: String, buffer: Option<usize>, fan_in: usize) -> Result<(), CmdError> {
+    let parser = gfa::GFAParserBuilder::all().build();
+    let tmp = match buffer {
+        Some(0) => {
+            return Err(CmdError::InvalidArgument(
+                "the buffer must hold at least one record".to_string(),
+            ))
+        }
+        Some(_) => {
+            let dir = Path::new(&output)
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or(Path::new("."));
+            Some(TempDir::new_in(dir, "pantools_sort").map_err(|_| CmdError::CreateFileError)?)
+        }
+        None => None,
+    };
+
+    let mut headers: Vec<Vec<u8>> = Vec::new();
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut runs: Vec<PathBuf> = Vec::new();
+    let mut lazy = gfa::LazySteps::new(&gfa);
+    binary::for_each_line(&gfa, |line| {
+        let line = line.trim().to_vec();
+        if line.is_empty() {
+            return Ok(());
+        }
+        let mut entity = match parser.parse_gfa_line(&line)? {
+            Some(entity) => entity,
+            None => return Ok(()),
+        };
+        // records of an index are written with the steps they stand for
+        let line = match lazy.load(&mut entity)? {
+            true => {
+                let mut loaded = Vec::new();
+                entity.write_line(&mut loaded)?;
+                loaded.trim_end().to_vec()
+            }
+            false => line,
+        };
+        match sort_key(entity) {
+            Some(key) => entries.push((key, line)),
+            None => headers.push(line),
+        }
+        if let (Some(limit), Some(dir)) = (buffer, &tmp) {
+            if entries.len() >= limit {
+                runs.push(spill(&mut entries, dir.path(), runs.len())?);
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut writer = BufWriter::new(File::create(&output).map_err(|_| CmdError::CreateFileError)?);
+    for line in &headers {
+        writer.write_all(line).map_err(|_| CmdError::WriteError)?;
+        writer.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
+    }
+    match &tmp {
+        Some(dir) if !runs.is_empty() => {
+            if !entries.is_empty() {
+                runs.push(spill(&mut entries, dir.path(), runs.len())?);
+            }
+            log::info!("Merging {} sorted runs", runs.len());
+            let runs = merge_passes(runs, dir.path(), fan_in, &parser)?;
+            merge_runs(&runs, &parser, &mut writer)?;
+        }
+        _ => {
+            entries.sort();
+            for (_, line) in &entries {
+                writer.write_all(line).map_err(|_| CmdError::WriteError)?;
+                writer.write_all(b"\n").map_err(|_| CmdError::WriteError)?;
+            }
+        }
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_sort() {
+        let temp_dir = TempDir::new("test_sort").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("sorted.gfa");
+        let gfa_data = b"W\tsample2\t0\tchr1\t0\t12\t>11>13\n\
+            S\t13\tCTTGATT\n\
+            L\t12\t-\t13\t+\t0M\n\
+            H\tVN:Z:1.1\n\
+            P\tsample3#0#chr1\t11+,12-\t*\n\
+            W\tsample1\t10\tchr1\t0\t5\t>11\n\
+            W\tsample1\t0\tchr1\t20\t27\t>13\n\
+            W\tsample1\t2\tchr1\t0\t5\t>11\n\
+            S\t11\tACCTT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t11\t-\t13\t-\t0M\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+            S\t12\tTCAAGG\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        let expected = "H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t11\t-\t13\t-\t0M\n\
+            L\t12\t-\t13\t+\t0M\n\
+            P\tsample3#0#chr1\t11+,12-\t*\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+            W\tsample1\t0\tchr1\t20\t27\t>13\n\
+            W\tsample1\t2\tchr1\t0\t5\t>11\n\
+            W\tsample1\t10\tchr1\t0\t5\t>11\n\
+            W\tsample2\t0\tchr1\t0\t12\t>11>13\n";
+
+        let gfa_file = gfa_file_path.to_str().unwrap().to_string();
+        let output = output_file_path.to_str().unwrap().to_string();
+        run(gfa_file.clone(), output.clone(), None).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+
+        run(gfa_file.clone(), output.clone(), Some(2)).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+
+        // one record per run, merged two at a time over several passes
+        sort(gfa_file.clone(), output.clone(), Some(1), 2).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+
+        // a binary graph sorts the same
+        let binary = format!("{}.pgb", gfa_file);
+        crate::cmd::convert::convert_binary(gfa_file.clone(), binary.clone()).unwrap();
+        run(binary, output.clone(), Some(3)).unwrap();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), expected);
+
+        // the records of an index are written with their steps
+        crate::cmd::index::build(&gfa_file).unwrap();
+        run(format!("{}.simple", gfa_file), output.clone(), None).unwrap();
+        let walks: String = expected
+            .lines()
+            .skip(7)
+            .map(|l| format!("{}\n", l))
+            .collect();
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), walks);
+    }
+}
@@ -0,0 +1,335 @@
+use crate::gfa::GfaWritable;
+use crate::{error::CmdError, gfa};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use log;
+
+/// Name of the output graph holding segments no walk or path traverses.
+const UNASSIGNED: &str = "unassigned";
+
+/// Read a two-column `contig<TAB>chromosome` file.
+fn read_mapping(path: &str) -> Result<HashMap<String, String>, CmdError> {
+    let reader = BufReader::new(File::open(path).map_err(CmdError::FileOpenError)?);
+    let mut mapping = HashMap::new();
+    for line in reader.lines() {
+        let line = line.map_err(CmdError::LineReadError)?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let contig = fields.next().ok_or(CmdError::ParseError)?;
+        let chrom = fields.next().ok_or(CmdError::ParseError)?;
+        mapping.insert(contig.to_owned(), chrom.to_owned());
+    }
+    Ok(mapping)
+}
+
+/// File name for the graph of `name`: characters other than letters,
+/// digits, `.`, `_`, `-` and `#` become `_`, and a suffix keeps it apart
+/// from the names already `used`.
+fn file_name(name: &str, used: &mut HashSet<String>) -> String {
+    let clean: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() || "._-#".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    let clean = match clean.trim_start_matches('.').is_empty() {
+        true => format!("_{}", clean),
+        false => clean,
+    };
+    let mut candidate = clean.clone();
+    let mut n = 1;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}_{}", clean, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// Split a graph into one self-contained GFA per chromosome in `outdir`.
+///
+/// Walks and paths are grouped by contig, renamed through the optional
+/// `mapping` file so unplaced contigs can join a chromosome. Segments go to
+/// every chromosome that traverses them; shared ones are listed in
+/// `shared_segments.tsv`. Links are kept where both ends are present;
+/// links between chromosomes or to untraversed segments go, with their end
+/// segments, to `unassigned.gfa` along with the untraversed segments.
+/// Chromosome names are made safe to use as file names.
+pub fn run(gfa: String, outdir: String, mapping: Option<String>) -> Result<(), CmdError> {
+    let mapping = match mapping {
+        Some(path) => read_mapping(&path)?,
+        None => HashMap::new(),
+    };
+    let gfa_parser = gfa::GFAParserBuilder::all().build();
+    let gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let group_of = |contig: &String| mapping.get(contig).unwrap_or(contig).clone();
+
+    let mut groups: Vec<String> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+    let mut walks: Vec<Vec<usize>> = Vec::new();
+    let mut paths: Vec<Vec<usize>> = Vec::new();
+    let mut segment_groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut index_of = |name: String, walks: &mut Vec<Vec<usize>>, paths: &mut Vec<Vec<usize>>| {
+        *group_index.entry(name.clone()).or_insert_with(|| {
+            groups.push(name);
+            walks.push(Vec::new());
+            paths.push(Vec::new());
+            groups.len() - 1
+        })
+    };
+
+    for (i, walk) in gfa_obj.walks.iter().enumerate() {
+        let g = index_of(group_of(&walk.chroms), &mut walks, &mut paths);
+        walks[g].push(i);
+        for node in walk.extract_node() {
+            let entry = segment_groups.entry(node).or_default();
+            if !entry.contains(&g) {
+                entry.push(g);
+            }
+        }
+    }
+    for (i, path) in gfa_obj.paths.iter().enumerate() {
+        let g = index_of(group_of(&path.chroms), &mut walks, &mut paths);
+        paths[g].push(i);
+        for node in path.extract_node() {
+            let entry = segment_groups.entry(node).or_default();
+            if !entry.contains(&g) {
+                entry.push(g);
+            }
+        }
+    }
+    // links between chromosomes, or with an end no walk or path traverses,
+    // are kept in the unassigned graph with their end segments
+    let mut links: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+    let mut cross_links: Vec<usize> = Vec::new();
+    for (i, link) in gfa_obj.links.iter().enumerate() {
+        let from = segment_groups.get(&link.from_segment);
+        let to = segment_groups.get(&link.to_segment);
+        let common: Vec<usize> = match (from, to) {
+            (Some(from), Some(to)) => from.iter().filter(|g| to.contains(g)).copied().collect(),
+            _ => Vec::new(),
+        };
+        match common.is_empty() {
+            true => cross_links.push(i),
+            false => common.iter().for_each(|&g| links[g].push(i)),
+        }
+    }
+    let mut segments: Vec<Vec<usize>> = vec![Vec::new(); groups.len()];
+    let mut unassigned_segments: Vec<usize> = Vec::new();
+    let cross_ends: HashSet<usize> = cross_links
+        .iter()
+        .flat_map(|&i| [gfa_obj.links[i].from_segment, gfa_obj.links[i].to_segment])
+        .collect();
+    for (i, segment) in gfa_obj.segments.iter().enumerate() {
+        match segment_groups.get(&segment.id) {
+            Some(gs) => gs.iter().for_each(|&g| segments[g].push(i)),
+            None => unassigned_segments.push(i),
+        }
+        if segment_groups.contains_key(&segment.id) && cross_ends.contains(&segment.id) {
+            unassigned_segments.push(i);
+        }
+    }
+    let mut names = groups.clone();
+    if !unassigned_segments.is_empty() || !cross_links.is_empty() {
+        names.push(UNASSIGNED.to_string());
+        segments.push(unassigned_segments);
+        links.push(cross_links);
+        walks.push(Vec::new());
+        paths.push(Vec::new());
+    }
+    log::info!("Splitting the graph into {} chromosomes", groups.len());
+
+    let outdir = Path::new(&outdir);
+    std::fs::create_dir_all(outdir).map_err(|_| CmdError::CreateFileError)?;
+    let mut used: HashSet<String> = HashSet::new();
+    for (g, name) in names.iter().enumerate() {
+        let file_name = file_name(name, &mut used);
+        if file_name != *name {
+            log::warn!("{} is written to {}.gfa", name, file_name);
+        }
+        let file = File::create(outdir.join(format!("{}.gfa", file_name)))
+            .map_err(|_| CmdError::CreateFileError)?;
+        let mut writer = BufWriter::new(file);
+        if !gfa_obj.headers.version.is_empty() {
+            gfa_obj.headers.write_line(&mut writer)?;
+        }
+        for &i in &segments[g] {
+            gfa_obj.segments[i].write_line(&mut writer)?;
+        }
+        for &i in &links[g] {
+            gfa_obj.links[i].write_line(&mut writer)?;
+        }
+        for &i in &paths[g] {
+            gfa_obj.paths[i].write_line(&mut writer)?;
+        }
+        for &i in &walks[g] {
+            gfa_obj.walks[i].write_line(&mut writer)?;
+        }
+        writer.flush().map_err(|_| CmdError::WriteError)?;
+        log::debug!(
+            "{}: {} segments, {} links, {} walks, {} paths",
+            name,
+            segments[g].len(),
+            links[g].len(),
+            walks[g].len(),
+            paths[g].len()
+        );
+    }
+
+    let shared: BTreeMap<usize, &Vec<usize>> = segment_groups
+        .iter()
+        .filter(|(_, gs)| gs.len() > 1)
+        .map(|(&id, gs)| (id, gs))
+        .collect();
+    let mut writer = BufWriter::new(
+        File::create(outdir.join("shared_segments.tsv")).map_err(|_| CmdError::CreateFileError)?,
+    );
+    writeln!(writer, "segment\tchromosomes").map_err(|_| CmdError::WriteError)?;
+    for (id, gs) in &shared {
+        let names: Vec<&str> = gs.iter().map(|&g| groups[g].as_str()).collect();
+        writeln!(writer, "{}\t{}", id, names.join(",")).map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+    if !shared.is_empty() {
+        log::warn!("{} segments are shared between chromosomes", shared.len());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_split() {
+        let temp_dir = TempDir::new("test_split").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let mapping_file_path = temp_dir.path().join("mapping.tsv");
+        let outdir = temp_dir.path().join("out");
+
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tACCTT\n\
+            S\t12\tTCAAGG\n\
+            S\t13\tCTTGATT\n\
+            S\t14\tAA\n\
+            S\t15\tC\n\
+            L\t11\t+\t12\t-\t0M\n\
+            L\t12\t-\t13\t+\t0M\n\
+            L\t13\t+\t14\t+\t0M\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n\
+            W\tsample2\t0\tchr2\t0\t9\t>13>14\n\
+            W\tsample2\t0\tchrUn_1\t0\t6\t>12\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        writeln!(File::create(&mapping_file_path).unwrap(), "chrUn_1\tchr2").unwrap();
+
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            outdir.to_str().unwrap().to_string(),
+            Some(mapping_file_path.to_str().unwrap().to_string()),
+        )
+        .unwrap();
+
+        let chr1 = std::fs::read_to_string(outdir.join("chr1.gfa")).unwrap();
+        assert_eq!(
+            chr1,
+            "H\tVN:Z:1.1\n\
+             S\t11\tACCTT\n\
+             S\t12\tTCAAGG\n\
+             L\t11\t+\t12\t-\t0M\n\
+             W\tsample1\t0\tchr1\t0\t11\t>11<12\n"
+        );
+        let chr2 = std::fs::read_to_string(outdir.join("chr2.gfa")).unwrap();
+        assert_eq!(
+            chr2,
+            "H\tVN:Z:1.1\n\
+             S\t12\tTCAAGG\n\
+             S\t13\tCTTGATT\n\
+             S\t14\tAA\n\
+             L\t12\t-\t13\t+\t0M\n\
+             L\t13\t+\t14\t+\t0M\n\
+             W\tsample2\t0\tchr2\t0\t9\t>13>14\n\
+             W\tsample2\t0\tchrUn_1\t0\t6\t>12\n"
+        );
+        let unassigned = std::fs::read_to_string(outdir.join("unassigned.gfa")).unwrap();
+        assert_eq!(unassigned, "H\tVN:Z:1.1\nS\t15\tC\n");
+        let shared = std::fs::read_to_string(outdir.join("shared_segments.tsv")).unwrap();
+        assert_eq!(shared, "segment\tchromosomes\n12\tchr1,chr2\n");
+    }
+
+    #[test]
+    fn test_split_cross_links() {
+        let temp_dir = TempDir::new("test_split_cross").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let outdir = temp_dir.path().join("out");
+        let gfa_data = b"H\tVN:Z:1.1\n\
+            S\t11\tA\n\
+            S\t12\tC\n\
+            S\t13\tG\n\
+            L\t11\t+\t12\t+\t0M\n\
+            L\t12\t+\t13\t+\t0M\n\
+            W\tsample\t0\tchr/1\t0\t1\t>11\n\
+            W\tsample\t0\tunassigned\t0\t1\t>12\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            outdir.to_str().unwrap().to_string(),
+            None,
+        )
+        .unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(outdir.join(name)).unwrap();
+        assert_eq!(
+            read("chr_1.gfa"),
+            "H\tVN:Z:1.1\nS\t11\tA\nW\tsample\t0\tchr/1\t0\t1\t>11\n"
+        );
+        assert_eq!(
+            read("unassigned.gfa"),
+            "H\tVN:Z:1.1\nS\t12\tC\nW\tsample\t0\tunassigned\t0\t1\t>12\n"
+        );
+        assert_eq!(
+            read("unassigned_1.gfa"),
+            "H\tVN:Z:1.1\n\
+             S\t11\tA\n\
+             S\t12\tC\n\
+             S\t13\tG\n\
+             L\t11\t+\t12\t+\t0M\n\
+             L\t12\t+\t13\t+\t0M\n"
+        );
+
+        // without walks or paths everything is unassigned
+        let gfa_data = b"H\tVN:Z:1.1\nS\t11\tA\nS\t12\tC\nL\t11\t+\t12\t+\t0M\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data)
+            .unwrap();
+        let outdir = temp_dir.path().join("out2");
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            outdir.to_str().unwrap().to_string(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(outdir.join("unassigned.gfa")).unwrap(),
+            std::str::from_utf8(gfa_data).unwrap()
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use crate::gfa::{GfaEntity, GfaWritable};
+use crate::{binary, error::CmdError, gfa};
+use bstr::io::BufReadExt;
+use bstr::ByteSlice;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+use log;
+
+/// Copy `gfa` to `output` line by line, handing every S-line to `f` and
+/// writing the segment it returns.
+fn rewrite_segments(
+    gfa: &str,
+    output: &str,
+    mut f: impl FnMut(gfa::Segment) -> Result<gfa::Segment, CmdError>,
+) -> Result<(), CmdError> {
+    let parser = gfa::GFAParserBuilder::new().get_segments(true).build();
+    let mut writer = BufWriter::new(File::create(output).map_err(|_| CmdError::CreateFileError)?);
+    binary::for_each_line(gfa, |line| {
+        if line.first() == Some(&b'S') {
+            if let Some(GfaEntity::Segment(segment)) = parser.parse_gfa_line(line)? {
+                return f(segment)?.write_line(&mut writer);
+            }
+        }
+        writer.write_all(line).map_err(|_| CmdError::WriteError)?;
+        writer.write_all(b"\n").map_err(|_| CmdError::WriteError)
+    })?;
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+    Ok(())
+}
+
+/// File next to a stripped GFA listing the segments whose `LN:i` tag was
+/// added by strip, one ID per line, so restore drops those tags again.
+fn added_ln_path(gfa: &str) -> String {
+    format!("{}.ln", gfa)
+}
+
+/// Replace every segment sequence by `*` with an `LN:i` tag and write the
+/// sequences to `fasta`, one record per segment named by its ID.
+///
+/// An existing `LN:i` tag keeps its position and must match the sequence;
+/// an added one is appended and its segment listed in `<output>.ln`.
+pub fn strip(gfa: String, output: String, fasta: String) -> Result<(), CmdError> {
+    let mut fasta_writer =
+        BufWriter::new(File::create(&fasta).map_err(|_| CmdError::CreateFileError)?);
+    let mut ln_writer = BufWriter::new(
+        File::create(added_ln_path(&output)).map_err(|_| CmdError::CreateFileError)?,
+    );
+    let mut stripped = 0;
+    rewrite_segments(&gfa, &output, |mut segment| {
+        if segment.sequence == b"*" {
+            return Ok(segment);
+        }
+        let len = segment.sequence.len();
+        match segment.tags.iter().find(|t| t.name == "LN") {
+            Some(tag) if tag.value.parse::<usize>().ok() != Some(len) => {
+                return Err(CmdError::InvalidArgument(format!(
+                    "segment {} has LN:i:{} but its sequence is {} bp",
+                    segment.id, tag.value, len
+                )));
+            }
+            Some(_) => {}
+            None => {
+                segment.tags.push(gfa::Tag {
+                    name: "LN".to_string(),
+                    kind: 'i',
+                    value: len.to_string(),
+                });
+                writeln!(ln_writer, "{}", segment.id).map_err(|_| CmdError::WriteError)?;
+            }
+        }
+        writeln!(fasta_writer, ">{}", segment.id).map_err(|_| CmdError::WriteError)?;
+        fasta_writer
+            .write_all(&segment.sequence)
+            .map_err(|_| CmdError::WriteError)?;
+        fasta_writer
+            .write_all(b"\n")
+            .map_err(|_| CmdError::WriteError)?;
+        segment.sequence = b"*".to_vec();
+        stripped += 1;
+        Ok(segment)
+    })?;
+    fasta_writer.flush().map_err(|_| CmdError::WriteError)?;
+    ln_writer.flush().map_err(|_| CmdError::WriteError)?;
+    log::info!("Stripped the sequences of {} segments", stripped);
+    Ok(())
+}
+
+/// Read a FASTA file keyed by segment ID, the first word of each header.
+fn read_fasta(path: &str) -> Result<HashMap<usize, Vec<u8>>, CmdError> {
+    let file = File::open(path).map_err(CmdError::FileOpenError)?;
+    let mut sequences: HashMap<usize, Vec<u8>> = HashMap::new();
+    let mut current: Option<usize> = None;
+    for line in BufReader::new(file).byte_lines() {
+        let line = line.map_err(CmdError::LineReadError)?;
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix(b">") {
+            let id = name
+                .fields()
+                .next()
+                .and_then(|n| n.to_str().ok())
+                .and_then(|n| n.parse::<usize>().ok())
+                .ok_or(CmdError::ParseError)?;
+            sequences.insert(id, Vec::new());
+            current = Some(id);
+        } else if !line.is_empty() {
+            let id = current.ok_or(CmdError::ParseError)?;
+            sequences.entry(id).or_default().extend_from_slice(line);
+        }
+    }
+    Ok(sequences)
+}
+
+/// Put the sequences of `fasta` back into the `*` segments of `gfa`.
+///
+/// The `LN:i` tag of a restored segment must match its sequence; it is
+/// dropped when `<gfa>.ln` lists the segment, as strip writes it, and kept
+/// otherwise. Segments missing from the FASTA keep their `*`.
+pub fn restore(gfa: String, fasta: String, output: String) -> Result<(), CmdError> {
+    let mut sequences = read_fasta(&fasta)?;
+    log::debug!("Read {} sequences from {}", sequences.len(), fasta);
+    let ln_path = added_ln_path(&gfa);
+    let added: HashSet<usize> = match std::path::Path::new(&ln_path).exists() {
+        true => super::read_node_list(&ln_path)?.into_iter().collect(),
+        false => {
+            log::warn!("{} not found, keeping every LN:i tag", ln_path);
+            HashSet::new()
+        }
+    };
+    let mut missing = 0;
+    rewrite_segments(&gfa, &output, |mut segment| {
+        if segment.sequence != b"*" {
+            return Ok(segment);
+        }
+        let sequence = match sequences.remove(&segment.id) {
+            Some(sequence) => sequence,
+            None => {
+                missing += 1;
+                return Ok(segment);
+            }
+        };
+        if segment.tags.iter().any(|t| t.name == "LN") && segment.length() != sequence.len() {
+            return Err(CmdError::InvalidArgument(format!(
+                "segment {} has LN:i:{} but its sequence is {} bp",
+                segment.id,
+                segment.length(),
+                sequence.len()
+            )));
+        }
+        if added.contains(&segment.id) {
+            segment.tags.retain(|t| t.name != "LN");
+        }
+        segment.sequence = sequence;
+        Ok(segment)
+    })?;
+    if missing > 0 {
+        log::warn!("{} segments have no sequence in {}", missing, fasta);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_strip_and_restore() {
+        let temp_dir = TempDir::new("test_strip").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let stripped_file_path = temp_dir.path().join("stripped.gfa");
+        let fasta_file_path = temp_dir.path().join("stripped.fa");
+        let restored_file_path = temp_dir.path().join("restored.gfa");
+        let gfa_data = "H\tVN:Z:1.1\n\
+            S\t11\tACCTT\tRC:i:3\n\
+            S\t12\tTCAAGG\tLN:i:6\tRC:i:4\n\
+            L\t11\t+\t12\t-\t0M\n\
+            W\tsample1\t0\tchr1\t0\t11\t>11<12\n";
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data.as_bytes())
+            .unwrap();
+
+        let stripped = stripped_file_path.to_str().unwrap().to_string();
+        let fasta = fasta_file_path.to_str().unwrap().to_string();
+        let restored = restored_file_path.to_str().unwrap().to_string();
+        strip(
+            gfa_file_path.to_str().unwrap().to_string(),
+            stripped.clone(),
+            fasta.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&stripped).unwrap(),
+            "H\tVN:Z:1.1\n\
+             S\t11\t*\tRC:i:3\tLN:i:5\n\
+             S\t12\t*\tLN:i:6\tRC:i:4\n\
+             L\t11\t+\t12\t-\t0M\n\
+             W\tsample1\t0\tchr1\t0\t11\t>11<12\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&fasta).unwrap(),
+            ">11\nACCTT\n>12\nTCAAGG\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.ln", stripped)).unwrap(),
+            "11\n"
+        );
+        let lengths = gfa::GFAParserBuilder::all()
+            .build()
+            .parse_file(stripped.clone())
+            .unwrap()
+            .get_segment_len();
+        assert_eq!(lengths[&12], 6);
+
+        restore(stripped.clone(), fasta.clone(), restored.clone()).unwrap();
+        assert_eq!(std::fs::read_to_string(&restored).unwrap(), gfa_data);
+
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(gfa_data.replace("LN:i:6", "LN:i:7").as_bytes())
+            .unwrap();
+        let err = strip(gfa_file_path.to_str().unwrap().to_string(), stripped, fasta);
+        assert!(matches!(err, Err(CmdError::InvalidArgument(_))));
+    }
+}
@@ -0,0 +1,378 @@
+use crate::{error::CmdError, gfa};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use log;
+
+const PALETTE: [&str; 12] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b", "#e377c2", "#7f7f7f",
+    "#bcbd22", "#17becf", "#aec7e8", "#ffbb78",
+];
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Collect the nodes within `context` link hops of the seed nodes.
+fn neighbourhood(links: &[gfa::Link], seeds: &[usize], context: usize) -> HashSet<usize> {
+    let mut adjacency: HashMap<usize, Vec<usize>> = HashMap::new();
+    for link in links {
+        adjacency
+            .entry(link.from_segment)
+            .or_default()
+            .push(link.to_segment);
+        adjacency
+            .entry(link.to_segment)
+            .or_default()
+            .push(link.from_segment);
+    }
+    let mut selected: HashSet<usize> = seeds.iter().copied().collect();
+    let mut queue: VecDeque<(usize, usize)> = seeds.iter().map(|&n| (n, 0)).collect();
+    while let Some((node, depth)) = queue.pop_front() {
+        if depth == context {
+            continue;
+        }
+        if let Some(next) = adjacency.get(&node) {
+            for &n in next {
+                if selected.insert(n) {
+                    queue.push_back((n, depth + 1));
+                }
+            }
+        }
+    }
+    selected
+}
+
+/// Render the segments and links of a GFA as DOT or GraphML, optionally
+/// restricted to the neighbourhood of the nodes listed in `node`.
+pub fn run(
+    gfa: String,
+    output: String,
+    format: String,
+    node: Option<String>,
+    context: usize,
+    color: bool,
+) -> Result<(), CmdError> {
+    if format != "dot" && format != "graphml" {
+        return Err(CmdError::InvalidArgument(format!(
+            "unknown format {}, expected dot or graphml",
+            format
+        )));
+    }
+    let gfa_parser = gfa::GFAParserBuilder::all().build();
+    let gfa_obj = gfa_parser.parse_file(gfa)?;
+    log::debug!("GFA file parsed successfully");
+
+    let selected = match node {
+        Some(node) => {
+            let seeds = super::read_node_list(&node)?;
+            let selected = neighbourhood(&gfa_obj.links, &seeds, context);
+            log::debug!(
+                "{} seed nodes expanded to {} nodes",
+                seeds.len(),
+                selected.len()
+            );
+            Some(selected)
+        }
+        None => None,
+    };
+    let keep = |id: usize| selected.as_ref().is_none_or(|s| s.contains(&id));
+
+    // sample index in order of first appearance, and the samples traversing each node
+    let mut samples: Vec<String> = Vec::new();
+    let mut traversals: HashMap<usize, Vec<usize>> = HashMap::new();
+    if color {
+        let mut sample_index: HashMap<String, usize> = HashMap::new();
+        let walks = gfa_obj.walks.iter().map(|w| (&w.sample, w.extract_node()));
+        let paths = gfa_obj.paths.iter().map(|p| (&p.sample, p.extract_node()));
+        for (sample, nodes) in walks.chain(paths) {
+            let idx = *sample_index.entry(sample.clone()).or_insert_with(|| {
+                samples.push(sample.clone());
+                samples.len() - 1
+            });
+            for n in nodes {
+                if !keep(n) {
+                    continue;
+                }
+                let entry = traversals.entry(n).or_default();
+                if !entry.contains(&idx) {
+                    entry.push(idx);
+                }
+            }
+        }
+        log::debug!("total number of samples: {}", samples.len());
+    }
+    let sample_color = |idx: usize| PALETTE[idx % PALETTE.len()];
+
+    let segments: Vec<&gfa::Segment> = gfa_obj.segments.iter().filter(|s| keep(s.id)).collect();
+    let links: Vec<&gfa::Link> = gfa_obj
+        .links
+        .iter()
+        .filter(|l| keep(l.from_segment) && keep(l.to_segment))
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(output).map_err(|_| CmdError::CreateFileError)?);
+    if format == "dot" {
+        writeln!(writer, "digraph pangenome {{").map_err(|_| CmdError::WriteError)?;
+        writeln!(writer, "    rankdir=LR;").map_err(|_| CmdError::WriteError)?;
+        writeln!(writer, "    node [shape=box];").map_err(|_| CmdError::WriteError)?;
+        for (idx, sample) in samples.iter().enumerate() {
+            writeln!(writer, "    // {}: {}", sample, sample_color(idx))
+                .map_err(|_| CmdError::WriteError)?;
+        }
+        for segment in &segments {
+            let mut attrs = format!("label=\"{}\\n{} bp\"", segment.id, segment.length());
+            if let Some(idx) = traversals.get(&segment.id) {
+                let colors: Vec<&str> = idx.iter().map(|&i| sample_color(i)).collect();
+                let names: Vec<&str> = idx.iter().map(|&i| samples[i].as_str()).collect();
+                let style = if colors.len() > 1 {
+                    "striped"
+                } else {
+                    "filled"
+                };
+                attrs.push_str(&format!(
+                    ", style=\"{}\", fillcolor=\"{}\", tooltip=\"{}\"",
+                    style,
+                    colors.join(":"),
+                    escape_dot(&names.join(","))
+                ));
+            }
+            writeln!(writer, "    \"{}\" [{}];", segment.id, attrs)
+                .map_err(|_| CmdError::WriteError)?;
+        }
+        for link in &links {
+            writeln!(
+                writer,
+                "    \"{}\" -> \"{}\" [taillabel=\"{}\", headlabel=\"{}\"];",
+                link.from_segment,
+                link.to_segment,
+                gfa::orient_char(link.from_orient),
+                gfa::orient_char(link.to_orient)
+            )
+            .map_err(|_| CmdError::WriteError)?;
+        }
+        writeln!(writer, "}}").map_err(|_| CmdError::WriteError)?;
+    } else {
+        writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")
+            .map_err(|_| CmdError::WriteError)?;
+        writeln!(
+            writer,
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+        )
+        .map_err(|_| CmdError::WriteError)?;
+        for (id, target, kind) in [
+            ("label", "node", "string"),
+            ("length", "node", "int"),
+            ("samples", "node", "string"),
+            ("color", "node", "string"),
+            ("from_orient", "edge", "string"),
+            ("to_orient", "edge", "string"),
+        ] {
+            writeln!(
+                writer,
+                "  <key id=\"{}\" for=\"{}\" attr.name=\"{}\" attr.type=\"{}\"/>",
+                id, target, id, kind
+            )
+            .map_err(|_| CmdError::WriteError)?;
+        }
+        writeln!(
+            writer,
+            "  <graph id=\"pangenome\" edgedefault=\"directed\">"
+        )
+        .map_err(|_| CmdError::WriteError)?;
+        for segment in &segments {
+            writeln!(writer, "    <node id=\"{}\">", segment.id)
+                .map_err(|_| CmdError::WriteError)?;
+            writeln!(
+                writer,
+                "      <data key=\"label\">{} ({} bp)</data>",
+                segment.id,
+                segment.length()
+            )
+            .map_err(|_| CmdError::WriteError)?;
+            writeln!(
+                writer,
+                "      <data key=\"length\">{}</data>",
+                segment.length()
+            )
+            .map_err(|_| CmdError::WriteError)?;
+            if let Some(idx) = traversals.get(&segment.id) {
+                let names: Vec<&str> = idx.iter().map(|&i| samples[i].as_str()).collect();
+                writeln!(
+                    writer,
+                    "      <data key=\"samples\">{}</data>",
+                    escape_xml(&names.join(","))
+                )
+                .map_err(|_| CmdError::WriteError)?;
+                writeln!(
+                    writer,
+                    "      <data key=\"color\">{}</data>",
+                    sample_color(idx[0])
+                )
+                .map_err(|_| CmdError::WriteError)?;
+            }
+            writeln!(writer, "    </node>").map_err(|_| CmdError::WriteError)?;
+        }
+        for link in &links {
+            writeln!(
+                writer,
+                "    <edge source=\"{}\" target=\"{}\">",
+                link.from_segment, link.to_segment
+            )
+            .map_err(|_| CmdError::WriteError)?;
+            writeln!(
+                writer,
+                "      <data key=\"from_orient\">{}</data>",
+                gfa::orient_char(link.from_orient)
+            )
+            .map_err(|_| CmdError::WriteError)?;
+            writeln!(
+                writer,
+                "      <data key=\"to_orient\">{}</data>",
+                gfa::orient_char(link.to_orient)
+            )
+            .map_err(|_| CmdError::WriteError)?;
+            writeln!(writer, "    </edge>").map_err(|_| CmdError::WriteError)?;
+        }
+        writeln!(writer, "  </graph>").map_err(|_| CmdError::WriteError)?;
+        writeln!(writer, "</graphml>").map_err(|_| CmdError::WriteError)?;
+    }
+    writer.flush().map_err(|_| CmdError::WriteError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    const GFA_DATA: &[u8] = b"H\tVN:Z:1.1\n\
+        S\t11\tACCTT\n\
+        S\t12\tTCAAGG\n\
+        S\t13\tCTTGATT\n\
+        S\t14\tAA\n\
+        L\t11\t+\t12\t-\t0M\n\
+        L\t12\t-\t13\t+\t0M\n\
+        L\t13\t+\t14\t+\t0M\n\
+        W\tsample1\t0\tchr1\t0\t18\t>11<12>13\n\
+        W\tsample2\t0\tchr1\t0\t14\t>11>13>14\n";
+
+    #[test]
+    fn test_viz_dot_subgraph() {
+        let temp_dir = TempDir::new("test_viz_dot").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let node_file_path = temp_dir.path().join("test.nodes");
+        let output_file_path = temp_dir.path().join("output.dot");
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(GFA_DATA)
+            .unwrap();
+        let mut node_file = File::create(&node_file_path).unwrap();
+        writeln!(node_file, "11").unwrap();
+
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            output_file_path.to_str().unwrap().to_string(),
+            "dot".to_string(),
+            Some(node_file_path.to_str().unwrap().to_string()),
+            1,
+            true,
+        )
+        .unwrap();
+
+        let output = std::fs::read_to_string(output_file_path).unwrap();
+        assert!(output.starts_with("digraph pangenome {"));
+        assert!(output.contains(
+            "\"11\" [label=\"11\\n5 bp\", style=\"striped\", \
+             fillcolor=\"#1f77b4:#ff7f0e\", tooltip=\"sample1,sample2\"];"
+        ));
+        assert!(output.contains(
+            "\"12\" [label=\"12\\n6 bp\", style=\"filled\", \
+             fillcolor=\"#1f77b4\", tooltip=\"sample1\"];"
+        ));
+        assert!(output.contains("\"11\" -> \"12\" [taillabel=\"+\", headlabel=\"-\"];"));
+        assert!(!output.contains("\"13\" ["));
+        assert!(!output.contains("\"12\" -> \"13\""));
+    }
+
+    #[test]
+    fn test_viz_graphml() {
+        let temp_dir = TempDir::new("test_viz_graphml").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let output_file_path = temp_dir.path().join("output.graphml");
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(GFA_DATA)
+            .unwrap();
+
+        run(
+            gfa_file_path.to_str().unwrap().to_string(),
+            output_file_path.to_str().unwrap().to_string(),
+            "graphml".to_string(),
+            None,
+            1,
+            false,
+        )
+        .unwrap();
+
+        let output = std::fs::read_to_string(output_file_path).unwrap();
+        assert_eq!(output.matches("<node id=").count(), 4);
+        assert_eq!(output.matches("<edge source=").count(), 3);
+        assert!(output.contains("<data key=\"label\">14 (2 bp)</data>"));
+        assert!(output.contains(
+            "<edge source=\"11\" target=\"12\">\n      \
+             <data key=\"from_orient\">+</data>\n      \
+             <data key=\"to_orient\">-</data>"
+        ));
+        assert!(!output.contains("<data key=\"samples\">"));
+    }
+
+    #[test]
+    fn test_viz_stripped() {
+        let temp_dir = TempDir::new("test_viz_stripped").unwrap();
+        let gfa_file_path = temp_dir.path().join("test.gfa");
+        let stripped_file_path = temp_dir.path().join("stripped.gfa");
+        let output_file_path = temp_dir.path().join("output.dot");
+        File::create(&gfa_file_path)
+            .unwrap()
+            .write_all(GFA_DATA)
+            .unwrap();
+        let stripped = stripped_file_path.to_str().unwrap().to_string();
+        crate::cmd::strip::strip(
+            gfa_file_path.to_str().unwrap().to_string(),
+            stripped.clone(),
+            temp_dir
+                .path()
+                .join("stripped.fa")
+                .to_str()
+                .unwrap()
+                .to_string(),
+        )
+        .unwrap();
+
+        run(
+            stripped,
+            output_file_path.to_str().unwrap().to_string(),
+            "dot".to_string(),
+            None,
+            1,
+            false,
+        )
+        .unwrap();
+
+        let output = std::fs::read_to_string(output_file_path).unwrap();
+        assert!(output.contains("\"12\" [label=\"12\\n6 bp\""));
+        assert!(output.contains("\"14\" [label=\"14\\n2 bp\""));
+    }
+}
@@ -22,4 +22,13 @@ pub enum CmdError {
 
     #[error("Unknown line type")]
     UnknownLineType,
+
+    #[error("Failed to parse JSON record: {0}")]
+    JsonError(serde_json::Error),
+
+    #[error("Invalid binary graph: {0}")]
+    BinaryFormatError(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
 }
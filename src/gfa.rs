@@ -1,7 +1,12 @@
+use crate::binary;
 use crate::error::CmdError;
+use crate::jsonl;
 use anyhow::Result;
 use bstr::{io::BufReadExt, ByteSlice};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
 
 /// Builder struct for GFAParsers
 #[derive(Debug, Default, Clone, Copy)]
@@ -63,10 +68,10 @@ impl GFAParserBuilder {
 }
 #[derive(Debug, Clone)]
 pub struct GFAParser {
-    segments: bool,
-    links: bool,
-    walks: bool,
-    paths: bool,
+    pub(crate) segments: bool,
+    pub(crate) links: bool,
+    pub(crate) walks: bool,
+    pub(crate) paths: bool,
 }
 
 impl Default for GFAParser {
@@ -130,9 +135,30 @@ impl GFAParser {
         }
     }
 
+    /// Parse one record of the JSON Lines representation written by
+    /// `convert --to jsonl`.
+    pub fn parse_jsonl_line(&self, bytes: &[u8]) -> Result<Option<GfaEntity>, CmdError> {
+        let entity = jsonl::parse_record(bytes)?;
+        let keep = match entity {
+            GfaEntity::Header(_) => true,
+            GfaEntity::Segment(_) => self.segments,
+            GfaEntity::Link(_) => self.links,
+            GfaEntity::Walk(_) => self.walks,
+            GfaEntity::Path(_) => self.paths,
+        };
+        Ok(if keep { Some(entity) } else { None })
+    }
+
+    /// Parse a GFA, JSON Lines or binary file. The steps of lazy walks and
+    /// paths, those of a `.simple` index, are loaded from the original GFA.
     pub fn parse_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<GFA, CmdError> {
         use std::{fs::File, io::BufReader};
 
+        if binary::is_binary(&path)? {
+            return binary::read_file(path, self);
+        }
+        let mut lazy = LazySteps::new(&path.as_ref().to_string_lossy());
+        let is_jsonl = path.as_ref().extension().is_some_and(|ext| ext == "jsonl");
         let file = File::open(path).map_err(CmdError::FileOpenError)?;
         let lines = BufReader::new(file).byte_lines();
 
@@ -140,7 +166,16 @@ impl GFAParser {
 
         for line in lines {
             let line = line.map_err(CmdError::LineReadError)?;
-            if let Some(entity) = self.parse_gfa_line(line.as_bytes())? {
+            let entity = if is_jsonl {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                self.parse_jsonl_line(line.as_bytes())?
+            } else {
+                self.parse_gfa_line(line.as_bytes())?
+            };
+            if let Some(mut entity) = entity {
+                lazy.load(&mut entity)?;
                 gfa.add_entity(entity);
             }
         }
@@ -149,6 +184,7 @@ impl GFAParser {
     }
 }
 
+#[derive(Default)]
 pub struct GFA {
     pub headers: Header,
     pub segments: Vec<Segment>,
@@ -176,24 +212,99 @@ impl GFA {
             GfaEntity::Path(path) => self.paths.push(path),
         }
     }
+    /// Write every record as GFA text: header, segments, links, paths, walks.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        if !self.headers.version.is_empty() {
+            self.headers.write_line(writer)?;
+        }
+        for segment in &self.segments {
+            segment.write_line(writer)?;
+        }
+        for link in &self.links {
+            link.write_line(writer)?;
+        }
+        for path in &self.paths {
+            path.write_line(writer)?;
+        }
+        for walk in &self.walks {
+            walk.write_line(writer)?;
+        }
+        Ok(())
+    }
     pub fn get_segment_len(&self) -> HashMap<usize, usize> {
         let mut len_map: HashMap<usize, usize> = HashMap::with_capacity(self.segments.len());
         for segment in self.segments.iter() {
-            len_map.insert(segment.id, segment.sequence.len());
+            len_map.insert(segment.id, segment.length());
         }
         len_map
     }
 }
 
+/// Optional `XX:T:value` field of a GFA line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tag {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: char,
+    pub value: String,
+}
+impl Tag {
+    pub fn parse(field: &[u8]) -> Result<Self, CmdError> {
+        let mut parts = field.splitn_str(3, b":");
+        let name = parts.next().ok_or(CmdError::ParseError)?;
+        let kind = parts.next().ok_or(CmdError::ParseError)?;
+        let value = parts.next().ok_or(CmdError::ParseError)?;
+        if name.len() != 2 || kind.len() != 1 {
+            return Err(CmdError::ParseError);
+        }
+        Ok(Tag {
+            name: String::from_utf8_lossy(name).into_owned(),
+            kind: kind[0] as char,
+            value: String::from_utf8_lossy(value).into_owned(),
+        })
+    }
+}
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.name, self.kind, self.value)
+    }
+}
+
+#[derive(Default)]
 pub struct Header {
     pub version: String,
     pub samples: Option<Vec<String>>,
+    pub tags: Vec<Tag>,
 }
 impl Header {
     fn new() -> Self {
         Header {
             version: String::new(),
             samples: None,
+            tags: Vec::new(),
+        }
+    }
+    /// Build a header from its `VN` value and remaining tags, deriving
+    /// `samples` the same way the line parser does.
+    pub fn from_tags(version: String, tags: Vec<Tag>) -> Self {
+        let samples: Vec<String> = tags
+            .iter()
+            .map(|t| {
+                if t.name == "RS" {
+                    t.value.clone()
+                } else {
+                    t.to_string()
+                }
+            })
+            .collect();
+        Header {
+            version,
+            samples: if samples.is_empty() {
+                None
+            } else {
+                Some(samples)
+            },
+            tags,
         }
     }
 }
@@ -201,12 +312,29 @@ impl Header {
 pub struct Segment {
     pub id: usize,
     pub sequence: Vec<u8>,
+    pub tags: Vec<Tag>,
+}
+impl Segment {
+    /// Sequence length, taken from the `LN:i` tag when the sequence is `*`.
+    pub fn length(&self) -> usize {
+        if self.sequence == b"*" {
+            self.tags
+                .iter()
+                .find(|t| t.name == "LN")
+                .and_then(|t| t.value.parse::<usize>().ok())
+                .unwrap_or(0)
+        } else {
+            self.sequence.len()
+        }
+    }
 }
 pub struct Link {
     pub from_segment: usize,
     pub from_orient: bool,
     pub to_segment: usize,
     pub to_orient: bool,
+    pub overlap: Vec<u8>,
+    pub tags: Vec<Tag>,
 }
 pub struct Path {
     pub sample: String,
@@ -214,7 +342,49 @@ pub struct Path {
     pub chroms: String,
     pub ranges: Option<Range>,
     pub unit: Vec<u8>,
+    pub overlaps: Option<Vec<u8>>,
+    pub tags: Vec<Tag>,
+}
+impl Path {
+    pub fn extract_node(&self) -> NodeIterator<'_> {
+        NodeIterator::new(&self.unit)
+    }
+    pub fn extract_step(&self) -> StepIterator<'_> {
+        StepIterator::new(&self.unit)
+    }
+    /// PanSN name of the path, `sample#haptype#chroms[:start-end]`.
+    pub fn name(&self) -> String {
+        match self.ranges {
+            Some(r) => format!(
+                "{}#{}#{}:{}-{}",
+                self.sample, self.haptype, self.chroms, r.start, r.end
+            ),
+            None => format!("{}#{}#{}", self.sample, self.haptype, self.chroms),
+        }
+    }
+    /// Whether the steps are the `*` placeholder of an index.
+    pub fn is_lazy(&self) -> bool {
+        self.unit == b"*"
+    }
+    /// Replace `*` steps by the steps of the same path in the original GFA.
+    pub fn load_steps(&mut self, source: &mut StepSource) -> Result<(), CmdError> {
+        if !self.is_lazy() {
+            return Ok(());
+        }
+        let path = match source.read_entity(&self.tags)? {
+            GfaEntity::Path(path) => path,
+            _ => return Err(stale_index(self.name())),
+        };
+        if path.name() != self.name() {
+            return Err(stale_index(self.name()));
+        }
+        self.unit = path.unit;
+        self.tags.retain(|t| t.name != OFFSET_TAG);
+        Ok(())
+    }
 }
+/// Iterates the segment IDs of a W-line (`>11<12`) or P-line
+/// (`11+,12-`) step list.
 pub struct NodeIterator<'a> {
     data: &'a [u8],
     pos: usize,
@@ -223,7 +393,7 @@ pub struct NodeIterator<'a> {
 }
 
 impl<'a> NodeIterator<'a> {
-    fn new(data: &'a [u8]) -> Self {
+    pub fn new(data: &'a [u8]) -> Self {
         NodeIterator {
             data,
             pos: 0,
@@ -241,13 +411,11 @@ impl<'a> Iterator for NodeIterator<'a> {
             self.pos += 1;
 
             match byte {
-                b'>' | b'<' => {
-                    if self.in_number {
-                        self.in_number = false;
-                        let number = self.current_number;
-                        self.current_number = 0;
-                        return Some(number);
-                    }
+                b'>' | b'<' | b'+' | b'-' | b',' if self.in_number => {
+                    self.in_number = false;
+                    let number = self.current_number;
+                    self.current_number = 0;
+                    return Some(number);
                 }
                 b'0'..=b'9' => {
                     self.current_number = self.current_number * 10 + (byte - b'0') as usize;
@@ -265,17 +433,220 @@ impl<'a> Iterator for NodeIterator<'a> {
     }
 }
 
+/// Oriented node of a walk or path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Step {
+    pub id: usize,
+    pub forward: bool,
+}
+
+/// Encode steps as a W-line step list, `>11<12`.
+pub fn walk_unit(steps: impl IntoIterator<Item = Step>) -> Vec<u8> {
+    let mut unit = Vec::new();
+    for step in steps {
+        unit.push(if step.forward { b'>' } else { b'<' });
+        unit.extend_from_slice(step.id.to_string().as_bytes());
+    }
+    unit
+}
+
+/// Encode steps as a P-line step list, `11+,12-`.
+pub fn path_unit(steps: impl IntoIterator<Item = Step>) -> Vec<u8> {
+    let mut unit = Vec::new();
+    for (i, step) in steps.into_iter().enumerate() {
+        if i > 0 {
+            unit.push(b',');
+        }
+        unit.extend_from_slice(step.id.to_string().as_bytes());
+        unit.push(if step.forward { b'+' } else { b'-' });
+    }
+    unit
+}
+
+/// Iterates the oriented steps of either a W-line (`>11<12`) or a
+/// P-line (`11+,12-`) step list.
+pub struct StepIterator<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StepIterator<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        StepIterator { data, pos: 0 }
+    }
+}
+
+impl<'a> Iterator for StepIterator<'a> {
+    type Item = Step;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut forward = None;
+        let mut id = 0;
+        let mut in_number = false;
+        while self.pos < self.data.len() {
+            let byte = self.data[self.pos];
+            match byte {
+                b'0'..=b'9' => {
+                    id = id * 10 + (byte - b'0') as usize;
+                    in_number = true;
+                }
+                b'>' | b'<' if in_number => break,
+                b'>' => forward = Some(true),
+                b'<' => forward = Some(false),
+                b'+' | b'-' if in_number => {
+                    self.pos += 1;
+                    return Some(Step {
+                        id,
+                        forward: byte == b'+',
+                    });
+                }
+                _ => {}
+            }
+            self.pos += 1;
+        }
+        if in_number {
+            return Some(Step {
+                id,
+                forward: forward.unwrap_or(true),
+            });
+        }
+        None
+    }
+}
+
+/// Tag written by `index` with the byte offset of a record in the original
+/// GFA, used to load the steps it replaced by `*`.
+pub const OFFSET_TAG: &str = "bo";
+
+/// The GFA a `.simple` index was built from, or `path` itself.
+pub fn graph_source(path: &str) -> &str {
+    path.strip_suffix(".simple").unwrap_or(path)
+}
+
+/// Reads the steps of lazy walks and paths, those whose steps are the `*`
+/// placeholder of a `.simple` index, from the GFA the index was built from.
+pub struct StepSource {
+    reader: std::io::BufReader<std::fs::File>,
+    parser: GFAParser,
+}
+
+impl StepSource {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> Result<Self, CmdError> {
+        let file = std::fs::File::open(path).map_err(CmdError::FileOpenError)?;
+        Ok(StepSource {
+            reader: std::io::BufReader::new(file),
+            parser: GFAParser::default(),
+        })
+    }
+
+    /// Open the GFA an index was built from, `x.gfa` for `x.gfa.simple`.
+    pub fn for_index(index: &str) -> Result<Self, CmdError> {
+        match index.strip_suffix(".simple") {
+            Some(gfa) => Self::open(gfa),
+            None => Err(CmdError::InvalidArgument(format!(
+                "{} has lazy steps but is not a .simple index",
+                index
+            ))),
+        }
+    }
+
+    fn read_entity(&mut self, tags: &[Tag]) -> Result<GfaEntity, CmdError> {
+        use std::io::{BufRead, Seek, SeekFrom};
+
+        let offset = tags
+            .iter()
+            .find(|t| t.name == OFFSET_TAG)
+            .and_then(|t| t.value.parse::<u64>().ok())
+            .ok_or(CmdError::ParseError)?;
+        self.reader
+            .seek(SeekFrom::Start(offset))
+            .map_err(CmdError::LineReadError)?;
+        let mut line = Vec::new();
+        self.reader
+            .read_until(b'\n', &mut line)
+            .map_err(CmdError::LineReadError)?;
+        self.parser
+            .parse_gfa_line(&line)?
+            .ok_or(CmdError::ParseError)
+    }
+}
+
+/// Loads the steps of the lazy records of a file as they are read, opening
+/// the GFA its index was built from at the first one.
+pub struct LazySteps {
+    index: String,
+    source: Option<StepSource>,
+}
+
+impl LazySteps {
+    pub fn new(index: &str) -> Self {
+        LazySteps {
+            index: index.to_string(),
+            source: None,
+        }
+    }
+
+    fn source(&mut self) -> Result<&mut StepSource, CmdError> {
+        if self.source.is_none() {
+            self.source = Some(StepSource::for_index(&self.index)?);
+        }
+        Ok(self.source.as_mut().unwrap())
+    }
+
+    /// Load the steps of `entity` if it is a lazy walk or path; returns
+    /// whether it was.
+    pub fn load(&mut self, entity: &mut GfaEntity) -> Result<bool, CmdError> {
+        match entity {
+            GfaEntity::Walk(walk) if walk.is_lazy() => walk.load_steps(self.source()?)?,
+            GfaEntity::Path(path) if path.is_lazy() => path.load_steps(self.source()?)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+fn stale_index(name: String) -> CmdError {
+    CmdError::InvalidArgument(format!("the index is out of date for {}", name))
+}
+
 pub struct Walk {
     pub sample: String,
     pub haptype: String,
     pub chroms: String,
     pub ranges: Range,
     pub unit: Vec<u8>,
+    pub tags: Vec<Tag>,
 }
 impl Walk {
-    pub fn extract_node(&self) -> NodeIterator {
+    pub fn extract_node(&self) -> NodeIterator<'_> {
         NodeIterator::new(&self.unit)
     }
+    pub fn extract_step(&self) -> StepIterator<'_> {
+        StepIterator::new(&self.unit)
+    }
+    /// PanSN name of the walk, `sample#haptype#chroms`.
+    pub fn name(&self) -> String {
+        format!("{}#{}#{}", self.sample, self.haptype, self.chroms)
+    }
+    /// Whether the steps are the `*` placeholder of an index.
+    pub fn is_lazy(&self) -> bool {
+        self.unit == b"*"
+    }
+    /// Replace `*` steps by the steps of the same walk in the original GFA.
+    pub fn load_steps(&mut self, source: &mut StepSource) -> Result<(), CmdError> {
+        if !self.is_lazy() {
+            return Ok(());
+        }
+        let walk = match source.read_entity(&self.tags)? {
+            GfaEntity::Walk(walk) => walk,
+            _ => return Err(stale_index(self.name())),
+        };
+        if walk.name() != self.name() || walk.ranges.start != self.ranges.start {
+            return Err(stale_index(self.name()));
+        }
+        self.unit = walk.unit;
+        self.tags.retain(|t| t.name != OFFSET_TAG);
+        Ok(())
+    }
 }
 #[derive(Debug, Clone, Copy)]
 pub struct Range {
@@ -298,14 +669,17 @@ impl GfaParsable for Header {
         };
 
         let mut samples: Vec<String> = vec![];
+        let mut tags: Vec<Tag> = vec![];
 
         for field in fields {
-            let sample_info: String;
-            if field.starts_with(b"RS:Z:") {
-                sample_info = String::from_utf8_lossy(&field[5..]).into_owned();
-            } else {
-                sample_info = String::from_utf8_lossy(&field).into_owned();
+            if let Ok(tag) = Tag::parse(field) {
+                tags.push(tag);
             }
+            let sample_info = if field.starts_with(b"RS:Z:") {
+                String::from_utf8_lossy(&field[5..]).into_owned()
+            } else {
+                String::from_utf8_lossy(field).into_owned()
+            };
             samples.push(sample_info);
         }
         let samples = if samples.is_empty() {
@@ -314,9 +688,26 @@ impl GfaParsable for Header {
             Some(samples)
         };
 
-        Ok(Header { version, samples })
+        Ok(Header {
+            version,
+            samples,
+            tags,
+        })
     }
 }
+/// Parse the optional fields of a line; malformed ones are skipped with a
+/// warning, as they were before tags were kept.
+fn parse_tags<'a>(fields: impl Iterator<Item = &'a [u8]>) -> Vec<Tag> {
+    fields
+        .filter_map(|field| match Tag::parse(field) {
+            Ok(tag) => Some(tag),
+            Err(_) => {
+                log::warn!("skipping malformed field {}", field.as_bstr());
+                None
+            }
+        })
+        .collect()
+}
 pub fn u8_slice_to_usize(slice: &[u8]) -> Result<usize, CmdError> {
     let mut num = 0;
 
@@ -332,13 +723,11 @@ impl GfaParsable for Segment {
         let sequence = fields
             .next()
             .ok_or_else(|| {
-                CmdError::LineReadError(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "No sequence found for segment.",
-                ))
+                CmdError::LineReadError(std::io::Error::other("No sequence found for segment."))
             })?
             .to_vec();
-        Ok(Segment { id, sequence })
+        let tags = parse_tags(fields);
+        Ok(Segment { id, sequence, tags })
     }
 }
 
@@ -346,37 +735,31 @@ impl GfaParsable for Link {
     fn parse_line<'a>(mut fields: impl Iterator<Item = &'a [u8]>) -> Result<Self, CmdError> {
         let from_segment: usize = u8_slice_to_usize(fields.next().ok_or(CmdError::EmptyLine)?)?;
         let tem = fields.next().ok_or(CmdError::EmptyLine)?;
-        let from_orient: bool;
-        match tem {
-            b"+" => {
-                from_orient = true;
-            }
-            b"-" => {
-                from_orient = false;
-            }
+        let from_orient = match tem {
+            b"+" => true,
+            b"-" => false,
             _ => {
                 return Err(CmdError::ParseError);
             }
-        }
+        };
         let to_segment: usize = u8_slice_to_usize(fields.next().ok_or(CmdError::EmptyLine)?)?;
         let tem = fields.next().ok_or(CmdError::EmptyLine)?;
-        let to_orient: bool;
-        match tem {
-            b"+" => {
-                to_orient = true;
-            }
-            b"-" => {
-                to_orient = false;
-            }
+        let to_orient = match tem {
+            b"+" => true,
+            b"-" => false,
             _ => {
                 return Err(CmdError::ParseError);
             }
-        }
+        };
+        let overlap = fields.next().unwrap_or(b"*").to_vec();
+        let tags = parse_tags(fields);
         Ok(Link {
             from_segment,
             from_orient,
             to_segment,
             to_orient,
+            overlap,
+            tags,
         })
     }
 }
@@ -391,12 +774,14 @@ impl GfaParsable for Walk {
         let start: usize = u8_slice_to_usize(fields.next().ok_or(CmdError::EmptyLine)?)? as usize;
         let end: usize = u8_slice_to_usize(fields.next().ok_or(CmdError::EmptyLine)?)? as usize;
         let unit: Vec<u8> = fields.next().ok_or(CmdError::EmptyLine)?.to_vec();
+        let tags = parse_tags(fields);
         Ok(Walk {
             sample,
             haptype,
             chroms: chr,
             ranges: Range { start, end },
             unit,
+            tags,
         })
     }
 }
@@ -432,16 +817,130 @@ impl GfaParsable for Path {
 
         let unit = fields.next().ok_or(CmdError::EmptyLine)?.to_vec();
 
+        // the overlap column is optional in the wild, tags never contain a CIGAR
+        let mut fields = fields.peekable();
+        let overlaps = match fields.peek() {
+            Some(field) if Tag::parse(field).is_err() => fields.next().map(|f| f.to_vec()),
+            _ => None,
+        };
+        let tags = parse_tags(fields);
+
         Ok(Path {
             sample,
             haptype,
             chroms,
             ranges,
             unit,
+            overlaps,
+            tags,
         })
     }
 }
 
+/// Serialise a parsed record back into its GFA line.
+pub trait GfaWritable {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError>;
+}
+
+fn write_tags<W: Write>(writer: &mut W, tags: &[Tag]) -> Result<(), CmdError> {
+    for tag in tags {
+        write!(writer, "\t{}", tag).map_err(|_| CmdError::WriteError)?;
+    }
+    writeln!(writer).map_err(|_| CmdError::WriteError)
+}
+
+pub fn orient_char(orient: bool) -> char {
+    if orient {
+        '+'
+    } else {
+        '-'
+    }
+}
+
+impl GfaWritable for Header {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        write!(writer, "H\tVN:Z:{}", self.version).map_err(|_| CmdError::WriteError)?;
+        write_tags(writer, &self.tags)
+    }
+}
+
+impl GfaWritable for Segment {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        write!(writer, "S\t{}\t", self.id).map_err(|_| CmdError::WriteError)?;
+        writer
+            .write_all(&self.sequence)
+            .map_err(|_| CmdError::WriteError)?;
+        write_tags(writer, &self.tags)
+    }
+}
+
+impl GfaWritable for Link {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        write!(
+            writer,
+            "L\t{}\t{}\t{}\t{}\t",
+            self.from_segment,
+            orient_char(self.from_orient),
+            self.to_segment,
+            orient_char(self.to_orient)
+        )
+        .map_err(|_| CmdError::WriteError)?;
+        writer
+            .write_all(&self.overlap)
+            .map_err(|_| CmdError::WriteError)?;
+        write_tags(writer, &self.tags)
+    }
+}
+
+impl GfaWritable for Walk {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        write!(
+            writer,
+            "W\t{}\t{}\t{}\t{}\t{}\t",
+            self.sample, self.haptype, self.chroms, self.ranges.start, self.ranges.end
+        )
+        .map_err(|_| CmdError::WriteError)?;
+        writer
+            .write_all(&self.unit)
+            .map_err(|_| CmdError::WriteError)?;
+        write_tags(writer, &self.tags)
+    }
+}
+
+impl GfaWritable for Path {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        write!(writer, "P\t{}\t", self.name()).map_err(|_| CmdError::WriteError)?;
+        writer
+            .write_all(&self.unit)
+            .map_err(|_| CmdError::WriteError)?;
+        match &self.overlaps {
+            Some(overlaps) => {
+                writer.write_all(b"\t").map_err(|_| CmdError::WriteError)?;
+                writer
+                    .write_all(overlaps)
+                    .map_err(|_| CmdError::WriteError)?;
+            }
+            None if !self.tags.is_empty() => {
+                writer.write_all(b"\t*").map_err(|_| CmdError::WriteError)?;
+            }
+            None => {}
+        }
+        write_tags(writer, &self.tags)
+    }
+}
+
+impl GfaWritable for GfaEntity {
+    fn write_line<W: Write>(&self, writer: &mut W) -> Result<(), CmdError> {
+        match self {
+            GfaEntity::Header(header) => header.write_line(writer),
+            GfaEntity::Segment(segment) => segment.write_line(writer),
+            GfaEntity::Link(link) => link.write_line(writer),
+            GfaEntity::Walk(walk) => walk.write_line(writer),
+            GfaEntity::Path(path) => path.write_line(writer),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -449,6 +948,15 @@ mod tests {
     use std::io::BufReader;
     use std::io::Cursor;
 
+    #[test]
+    fn test_node_iterator() {
+        let ids = |unit: &[u8]| NodeIterator::new(unit).collect::<Vec<usize>>();
+        assert_eq!(ids(b">11<12>13"), vec![11, 12, 13]);
+        assert_eq!(ids(b"11+,12-,13+"), vec![11, 12, 13]);
+        assert_eq!(ids(b"11+"), vec![11]);
+        assert!(ids(b"").is_empty());
+    }
+
     #[test]
     fn test_parse_p_gfa1() {
         let gfa_data = b"H\tVN:Z:1.0\n\
@@ -558,6 +1066,8 @@ mod tests {
         // Check path
         assert_eq!(gfa.paths[0].sample, "14");
         assert_eq!(gfa.paths[0].chroms, "chr1");
+        let unit = gfa.paths[0].extract_node().collect::<Vec<usize>>();
+        assert_eq!(unit, vec![11_usize, 12_usize, 13_usize]);
 
         // More detailed check for the ranges
         assert!(gfa.paths[0].ranges.is_some());
@@ -633,4 +1143,21 @@ mod tests {
         assert_eq!(ranges.start, 0, "The start of the range should be 0");
         assert_eq!(ranges.end, 18, "The end of the range should be 18");
     }
+
+    #[test]
+    fn test_parse_malformed_tags() {
+        let parser = GFAParser::default();
+        let segment = match parser.parse_gfa_line(b"S\t11\tACCTT\tLN:i:5\tjunk") {
+            Ok(Some(GfaEntity::Segment(segment))) => segment,
+            _ => panic!("expected a segment"),
+        };
+        assert_eq!(segment.tags.len(), 1);
+        assert_eq!(segment.tags[0].name, "LN");
+
+        let walk = match parser.parse_gfa_line(b"W\tsample\t0\tchr1\t0\t5\t>11\tx:y") {
+            Ok(Some(GfaEntity::Walk(walk))) => walk,
+            _ => panic!("expected a walk"),
+        };
+        assert!(walk.tags.is_empty());
+    }
 }
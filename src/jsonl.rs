@@ -0,0 +1,285 @@
+use crate::error::CmdError;
+use crate::gfa::{self, GfaEntity, Tag};
+use serde::{Deserialize, Serialize};
+
+/// Oriented step of a walk or path, `{"id":11,"orient":"+"}`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub id: usize,
+    pub orient: char,
+}
+
+/// One GFA line as a JSON object, tagged by the GFA record type.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Record {
+    #[serde(rename = "H")]
+    Header {
+        version: String,
+        #[serde(default)]
+        tags: Vec<Tag>,
+    },
+    #[serde(rename = "S")]
+    Segment {
+        id: usize,
+        sequence: String,
+        #[serde(default)]
+        length: usize,
+        #[serde(default)]
+        tags: Vec<Tag>,
+    },
+    #[serde(rename = "L")]
+    Link {
+        from: usize,
+        from_orient: char,
+        to: usize,
+        to_orient: char,
+        overlap: String,
+        #[serde(default)]
+        tags: Vec<Tag>,
+    },
+    #[serde(rename = "W")]
+    Walk {
+        sample: String,
+        haptype: String,
+        chrom: String,
+        start: usize,
+        end: usize,
+        steps: Vec<Step>,
+        #[serde(default)]
+        tags: Vec<Tag>,
+    },
+    #[serde(rename = "P")]
+    Path {
+        #[serde(default)]
+        name: String,
+        sample: String,
+        haptype: u8,
+        chrom: String,
+        start: Option<usize>,
+        end: Option<usize>,
+        steps: Vec<Step>,
+        overlaps: Option<String>,
+        #[serde(default)]
+        tags: Vec<Tag>,
+    },
+}
+
+fn to_steps(steps: gfa::StepIterator) -> Vec<Step> {
+    steps
+        .map(|s| Step {
+            id: s.id,
+            orient: gfa::orient_char(s.forward),
+        })
+        .collect()
+}
+
+fn parse_orient(orient: char) -> Result<bool, CmdError> {
+    match orient {
+        '+' => Ok(true),
+        '-' => Ok(false),
+        _ => Err(CmdError::ParseError),
+    }
+}
+
+fn from_steps(steps: &[Step]) -> Result<Vec<gfa::Step>, CmdError> {
+    steps
+        .iter()
+        .map(|s| {
+            Ok(gfa::Step {
+                id: s.id,
+                forward: parse_orient(s.orient)?,
+            })
+        })
+        .collect()
+}
+
+impl From<&GfaEntity> for Record {
+    fn from(entity: &GfaEntity) -> Self {
+        match entity {
+            GfaEntity::Header(h) => Record::Header {
+                version: h.version.clone(),
+                tags: h.tags.clone(),
+            },
+            GfaEntity::Segment(s) => Record::Segment {
+                id: s.id,
+                sequence: String::from_utf8_lossy(&s.sequence).into_owned(),
+                length: s.length(),
+                tags: s.tags.clone(),
+            },
+            GfaEntity::Link(l) => Record::Link {
+                from: l.from_segment,
+                from_orient: gfa::orient_char(l.from_orient),
+                to: l.to_segment,
+                to_orient: gfa::orient_char(l.to_orient),
+                overlap: String::from_utf8_lossy(&l.overlap).into_owned(),
+                tags: l.tags.clone(),
+            },
+            GfaEntity::Walk(w) => Record::Walk {
+                sample: w.sample.clone(),
+                haptype: w.haptype.clone(),
+                chrom: w.chroms.clone(),
+                start: w.ranges.start,
+                end: w.ranges.end,
+                steps: to_steps(w.extract_step()),
+                tags: w.tags.clone(),
+            },
+            GfaEntity::Path(p) => Record::Path {
+                name: p.name(),
+                sample: p.sample.clone(),
+                haptype: p.haptype,
+                chrom: p.chroms.clone(),
+                start: p.ranges.map(|r| r.start),
+                end: p.ranges.map(|r| r.end),
+                steps: to_steps(p.extract_step()),
+                overlaps: p
+                    .overlaps
+                    .as_ref()
+                    .map(|o| String::from_utf8_lossy(o).into_owned()),
+                tags: p.tags.clone(),
+            },
+        }
+    }
+}
+
+impl TryFrom<Record> for GfaEntity {
+    type Error = CmdError;
+    fn try_from(record: Record) -> Result<Self, CmdError> {
+        let entity = match record {
+            Record::Header { version, tags } => {
+                GfaEntity::Header(gfa::Header::from_tags(version, tags))
+            }
+            Record::Segment {
+                id,
+                sequence,
+                length,
+                mut tags,
+            } => {
+                // a stripped segment keeps its length only in this field
+                if sequence == "*" && length > 0 && !tags.iter().any(|t| t.name == "LN") {
+                    tags.push(Tag {
+                        name: "LN".to_string(),
+                        kind: 'i',
+                        value: length.to_string(),
+                    });
+                }
+                GfaEntity::Segment(gfa::Segment {
+                    id,
+                    sequence: sequence.into_bytes(),
+                    tags,
+                })
+            }
+            Record::Link {
+                from,
+                from_orient,
+                to,
+                to_orient,
+                overlap,
+                tags,
+            } => GfaEntity::Link(gfa::Link {
+                from_segment: from,
+                from_orient: parse_orient(from_orient)?,
+                to_segment: to,
+                to_orient: parse_orient(to_orient)?,
+                overlap: overlap.into_bytes(),
+                tags,
+            }),
+            Record::Walk {
+                sample,
+                haptype,
+                chrom,
+                start,
+                end,
+                steps,
+                tags,
+            } => {
+                let unit = gfa::walk_unit(from_steps(&steps)?);
+                GfaEntity::Walk(gfa::Walk {
+                    sample,
+                    haptype,
+                    chroms: chrom,
+                    ranges: gfa::Range { start, end },
+                    unit,
+                    tags,
+                })
+            }
+            Record::Path {
+                sample,
+                haptype,
+                chrom,
+                start,
+                end,
+                steps,
+                overlaps,
+                tags,
+                ..
+            } => {
+                let unit = gfa::path_unit(from_steps(&steps)?);
+                let ranges = match (start, end) {
+                    (Some(start), Some(end)) => Some(gfa::Range { start, end }),
+                    _ => None,
+                };
+                GfaEntity::Path(gfa::Path {
+                    sample,
+                    haptype,
+                    chroms: chrom,
+                    ranges,
+                    unit,
+                    overlaps: overlaps.map(String::into_bytes),
+                    tags,
+                })
+            }
+        };
+        Ok(entity)
+    }
+}
+
+/// Parse one JSON Lines record into a `GfaEntity`.
+pub fn parse_record(bytes: &[u8]) -> Result<GfaEntity, CmdError> {
+    let record: Record = serde_json::from_slice(bytes).map_err(CmdError::JsonError)?;
+    GfaEntity::try_from(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gfa::{GFAParser, GfaWritable};
+
+    #[test]
+    fn test_record_round_trip() {
+        let parser = GFAParser::default();
+        let lines: [&[u8]; 5] = [
+            b"H\tVN:Z:1.1\tRS:Z:sample1",
+            b"S\t11\tACCTT\tLN:i:5",
+            b"L\t11\t+\t12\t-\t0M\tRC:i:3",
+            b"W\tsample1\t0\tchr1\t0\t11\t>11<12\tWT:Z:x",
+            b"P\tsample2#1#chr2:5-16\t11+,12-\t0M",
+        ];
+        for line in lines {
+            let entity = parser.parse_gfa_line(line).unwrap().unwrap();
+            let json = serde_json::to_vec(&Record::from(&entity)).unwrap();
+            let back = parse_record(&json).unwrap();
+            let mut written = Vec::new();
+            back.write_line(&mut written).unwrap();
+            assert_eq!(written, [line, b"\n"].concat());
+        }
+
+        let stripped = br#"{"type":"S","id":11,"sequence":"*","length":5}"#;
+        match parse_record(stripped).unwrap() {
+            GfaEntity::Segment(segment) => assert_eq!(segment.length(), 5),
+            _ => unreachable!(),
+        }
+
+        let walk = parser
+            .parse_gfa_line(b"W\tsample1\t0\tchr1\t0\t11\t>11<12")
+            .unwrap()
+            .unwrap();
+        let json = serde_json::to_string(&Record::from(&walk)).unwrap();
+        assert_eq!(
+            json,
+            "{\"type\":\"W\",\"sample\":\"sample1\",\"haptype\":\"0\",\"chrom\":\"chr1\",\
+             \"start\":0,\"end\":11,\"steps\":[{\"id\":11,\"orient\":\"+\"},\
+             {\"id\":12,\"orient\":\"-\"}],\"tags\":[]}"
+        );
+    }
+}
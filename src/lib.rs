@@ -1,5 +1,7 @@
+pub mod binary;
 pub mod cmd;
 pub mod error;
 pub mod gfa;
+pub mod jsonl;
 pub mod logging;
 pub mod resource;
@@ -19,8 +19,8 @@ pub fn init_logging() {
                 record
                     .file()
                     .unwrap_or("None")
-                    .split('/')
-                    .last()
+                    .rsplit('/')
+                    .next()
                     .unwrap_or("None"),
                 record.line().unwrap_or(0),
                 style.value(record.args())
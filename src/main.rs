@@ -1,11 +1,22 @@
 use clap::{Parser, Subcommand};
+use pantools::binary;
+use pantools::cmd::bed;
+use pantools::cmd::classify;
 use pantools::cmd::convert;
+use pantools::cmd::edges;
+use pantools::cmd::growth;
 use pantools::cmd::index;
+use pantools::cmd::join;
+use pantools::cmd::merge;
 use pantools::cmd::pav;
+use pantools::cmd::renumber;
+use pantools::cmd::sort;
+use pantools::cmd::split;
+use pantools::cmd::strip;
+use pantools::cmd::viz;
 use pantools::error::CmdError;
 use pantools::logging;
 use pantools::resource;
-use log;
 
 const VERSION: &str = "version 0.1";
 
@@ -37,6 +48,14 @@ enum Subcli {
         /// W2P flag <True 1 else False 0>
         #[arg(short = 'i', default_value = "1")]
         i: String,
+
+        /// Target format <1.0|1.1|jsonl|pgb|gaf|gfa>, overrides -i
+        #[arg(long = "to")]
+        to: Option<String>,
+
+        /// Convert the graph with abutting walk fragments joined, as join -f gfa
+        #[arg(long = "join")]
+        join: bool,
     },
     /// Output PAV matrix of node list
     pav {
@@ -44,13 +63,106 @@ enum Subcli {
         #[arg(short = 'g', long = "gfa", required = true)]
         gfa: String,
 
-        /// Input node list
-        #[arg(short = 'n', long = "node", required = true)]
-        node: String,
+        /// Input node list, every segment of the graph by default
+        #[arg(short = 'n', long = "node", conflicts_with = "group")]
+        node: Option<String>,
 
         /// Output PAV matrix
         #[arg(short = 'o', long = "output", required = true)]
         output: String,
+
+        /// Report base pairs, segment length times copies, instead of copies
+        #[arg(long = "bp", conflicts_with = "group")]
+        bp: bool,
+
+        /// Matrix columns <sample|haplotype|contig>
+        #[arg(short = 'c', long = "columns", default_value = "sample")]
+        columns: String,
+
+        /// Orientation of traversals <none|split|combined>
+        #[arg(short = 's', long = "strand", default_value = "none")]
+        strand: String,
+
+        /// Input group<TAB>node file, one row per group, bp fractions in <output>.bp.tsv
+        #[arg(long = "group")]
+        group: Option<String>,
+
+        /// Fraction of a group's nodes, and of its bp, to call it present
+        #[arg(short = 't', long = "threshold", requires = "group")]
+        threshold: Option<f64>,
+
+        /// Reference sample or walk, one row per window along it
+        #[arg(short = 'r', long = "reference", conflicts_with_all = ["node", "group"])]
+        reference: Option<String>,
+
+        /// Window size in bp along the reference
+        #[arg(short = 'w', long = "window", default_value = "10000")]
+        window: usize,
+
+        /// Node matrix format <tsv|mtx|bits|npy>: bits is bit-packed presence, not compressed;
+        /// mtx, bits and npy get <output>.rows and <output>.cols labels
+        #[arg(short = 'f', long = "format", default_value = "tsv")]
+        format: String,
+
+        /// Input sample list, columns in its order and other samples left out
+        #[arg(long = "samples")]
+        samples: Option<String>,
+
+        /// Count walks with abutting fragments joined, as join -f gfa
+        #[arg(long = "join")]
+        join: bool,
+    },
+    /// Classify nodes as core, soft-core, dispensable or private
+    classify {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output table, the class of every node is written to <output>.nodes
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Count nodes over <sample|haplotype>
+        #[arg(short = 'c', long = "columns", default_value = "sample")]
+        columns: String,
+
+        /// Fraction of samples a soft-core node is found in
+        #[arg(long = "soft-core", default_value = "0.95")]
+        soft_core: f64,
+
+        /// Largest number of samples a private node is found in
+        #[arg(long = "private", default_value = "1")]
+        private: usize,
+    },
+    /// Output pangenome growth curves and a Heaps' law fit
+    growth {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output table, the Heaps' law fit is written to <output>.heaps
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Count nodes over <sample|haplotype>
+        #[arg(short = 'c', long = "columns", default_value = "sample")]
+        columns: String,
+
+        /// Curve computation <exact|permutation>
+        #[arg(short = 'm', long = "method", default_value = "exact")]
+        method: String,
+
+        /// Number of random sample orders
+        #[arg(short = 'p', long = "permutations", default_value = "100")]
+        permutations: usize,
+
+        /// Seed of the random sample orders
+        #[arg(long = "seed", default_value = "42")]
+        seed: u64,
+
+        /// Count base pairs instead of nodes
+        #[arg(long = "bp")]
+        bp: bool,
     },
     /// Build index for GFA
     index {
@@ -58,6 +170,162 @@ enum Subcli {
         #[arg(short = 'g', long = "gfa", required = true)]
         gfa: String,
     },
+    /// Renumber segment IDs into a compact ID space
+    renumber {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output GFA file, the ID table is written to <output>.map
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// New ID order <id|topo|path:NAME>
+        #[arg(long = "order", default_value = "id")]
+        order: String,
+
+        /// Node list to translate into <node>.renumbered
+        #[arg(short = 'n', long = "node")]
+        node: Option<String>,
+    },
+    /// Split a GFA into one graph per chromosome
+    split {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output directory
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Two-column contig to chromosome mapping for unplaced contigs
+        #[arg(short = 'm', long = "mapping")]
+        mapping: Option<String>,
+    },
+    /// Merge several GFA files into one GFA1.1
+    merge {
+        /// Input GFA files
+        #[arg(short = 'g', long = "gfa", required = true, num_args = 1..)]
+        gfa: Vec<String>,
+
+        /// Output GFA file, the ID offsets are written to <output>.offsets
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Keep segment IDs and write segments shared between inputs once
+        #[arg(long = "shared")]
+        shared: bool,
+    },
+    /// Output weighted edge and node tables for network analysis
+    edges {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output prefix for <output>.edges.tsv and <output>.nodes.tsv
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+    },
+    /// Output the position of every node along each walk and path as BED
+    bed {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output BED file
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Only output walks and paths of these samples
+        #[arg(short = 's', long = "sample", num_args = 1..)]
+        sample: Vec<String>,
+
+        /// Only output the nodes in this list
+        #[arg(short = 'n', long = "node")]
+        node: Option<String>,
+    },
+    /// Join fragmented walks of the same sample, haptype and contig
+    join {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output file
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Output format <report|summary|gfa>
+        #[arg(short = 'f', long = "format", default_value = "report")]
+        format: String,
+    },
+    /// Sort GFA records into a canonical order
+    sort {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output GFA file
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Hold at most this many records in memory, sorting in runs on disk
+        #[arg(short = 'b', long = "buffer")]
+        buffer: Option<usize>,
+    },
+    /// Replace segment sequences by LN:i tags and write them to a FASTA file
+    strip {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output GFA file; segments given an LN:i tag are listed in <output>.ln
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Output FASTA file, <output>.fa by default
+        #[arg(short = 'f', long = "fasta")]
+        fasta: Option<String>,
+    },
+    /// Put segment sequences from a FASTA file back into a stripped GFA
+    restore {
+        /// Input GFA file; LN:i tags of the segments listed in <gfa>.ln are dropped
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// FASTA file written by strip
+        #[arg(short = 'f', long = "fasta", required = true)]
+        fasta: String,
+
+        /// Output GFA file
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+    },
+    /// Render the graph or a subgraph as DOT or GraphML
+    viz {
+        /// Input GFA file
+        #[arg(short = 'g', long = "gfa", required = true)]
+        gfa: String,
+
+        /// Output file
+        #[arg(short = 'o', long = "output", required = true)]
+        output: String,
+
+        /// Output format <dot|graphml>
+        #[arg(short = 'f', long = "format", default_value = "dot")]
+        format: String,
+
+        /// Only render the neighbourhood of the nodes in this list
+        #[arg(short = 'n', long = "node")]
+        node: Option<String>,
+
+        /// Number of link hops around the listed nodes
+        #[arg(short = 'c', long = "context", default_value = "1")]
+        context: usize,
+
+        /// Colour nodes by the samples whose walks traverse them
+        #[arg(long = "color")]
+        color: bool,
+    },
 }
 
 #[warn(unused_imports)]
@@ -74,20 +342,151 @@ fn main() -> Result<(), CmdError> {
     // 2024/05/13 12:18 [ERROR]main.rs:38   This is an error message
     let arg: Args = Args::parse();
     match arg.command {
-        Subcli::convert { input, output, i } => {
-            let i = i.parse::<u32>().unwrap();
-            if i == 0 {
-                convert::convert_1_1(input, output)?
-            } else {
-                convert::convert_1_0(input, output)?
+        Subcli::convert {
+            input,
+            output,
+            i,
+            to,
+            join,
+        } => {
+            let joined = match join {
+                true => Some(join::joined_graph(&input)?),
+                false => None,
+            };
+            let input = joined.as_ref().map_or(input, |(_, path)| path.clone());
+            match to.as_deref() {
+                Some("1.0") => convert::convert_1_0(input, output)?,
+                Some("1.1") => convert::convert_1_1(input, output)?,
+                Some("jsonl") => convert::convert_jsonl(input, output)?,
+                Some("pgb") => convert::convert_binary(input, output)?,
+                Some("gaf") => convert::convert_gaf(input, output)?,
+                Some("gfa") => {
+                    if binary::is_binary(&input)? {
+                        convert::convert_from_binary(input, output)?
+                    } else {
+                        convert::convert_from_jsonl(input, output)?
+                    }
+                }
+                Some(other) => {
+                    return Err(CmdError::InvalidArgument(format!(
+                        "unknown target format {}, expected 1.0, 1.1, jsonl, pgb, gaf or gfa",
+                        other
+                    )))
+                }
+                None => {
+                    let i = i.parse::<u32>().unwrap();
+                    if i == 0 {
+                        convert::convert_1_1(input, output)?
+                    } else {
+                        convert::convert_1_0(input, output)?
+                    }
+                }
             }
         }
-        Subcli::pav { gfa, node, output } => pav::run(gfa, node, output)?,
+        Subcli::pav {
+            gfa,
+            node,
+            output,
+            bp,
+            columns,
+            strand,
+            group,
+            threshold,
+            reference,
+            window,
+            format,
+            samples,
+            join,
+        } => {
+            let joined = match join {
+                true => Some(join::joined_graph(&gfa)?),
+                false => None,
+            };
+            let gfa = joined.as_ref().map_or(gfa, |(_, path)| path.clone());
+            pav::run(
+                gfa,
+                node,
+                output,
+                pav::PavOptions {
+                    bp,
+                    columns,
+                    strand,
+                    group,
+                    threshold,
+                    reference,
+                    window,
+                    format,
+                    samples,
+                },
+            )?
+        }
+        Subcli::classify {
+            gfa,
+            output,
+            columns,
+            soft_core,
+            private,
+        } => classify::run(gfa, output, columns, soft_core, private)?,
+        Subcli::growth {
+            gfa,
+            output,
+            columns,
+            method,
+            permutations,
+            seed,
+            bp,
+        } => growth::run(gfa, output, columns, method, permutations, seed, bp)?,
         Subcli::index { gfa } => {
             index::build(&gfa)?;
         }
+        Subcli::renumber {
+            gfa,
+            output,
+            order,
+            node,
+        } => renumber::run(gfa, output, order, node)?,
+        Subcli::split {
+            gfa,
+            output,
+            mapping,
+        } => split::run(gfa, output, mapping)?,
+        Subcli::merge {
+            gfa,
+            output,
+            shared,
+        } => merge::run(gfa, output, shared)?,
+        Subcli::edges { gfa, output } => edges::run(gfa, output)?,
+        Subcli::bed {
+            gfa,
+            output,
+            sample,
+            node,
+        } => bed::run(gfa, output, sample, node)?,
+        Subcli::join {
+            gfa,
+            output,
+            format,
+        } => join::run(gfa, output, format)?,
+        Subcli::sort {
+            gfa,
+            output,
+            buffer,
+        } => sort::run(gfa, output, buffer)?,
+        Subcli::strip { gfa, output, fasta } => {
+            let fasta = fasta.unwrap_or_else(|| format!("{}.fa", output));
+            strip::strip(gfa, output, fasta)?;
+        }
+        Subcli::restore { gfa, fasta, output } => strip::restore(gfa, fasta, output)?,
+        Subcli::viz {
+            gfa,
+            output,
+            format,
+            node,
+            context,
+            color,
+        } => viz::run(gfa, output, format, node, context, color)?,
     };
-    println!("{}", format!("Done!, pantools {}", VERSION));
+    println!("Done!, pantools {}", VERSION);
     resource::gather_resources();
     Ok(())
 }